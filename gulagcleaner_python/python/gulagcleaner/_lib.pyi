@@ -0,0 +1,99 @@
+from typing import Any, Optional, Tuple
+
+from typing_extensions import Buffer
+
+class GulagCleanerError(Exception):
+    """Base class of the errors raised while cleaning a PDF."""
+
+class InvalidPdfError(GulagCleanerError):
+    """The data could not be read or written as a PDF document."""
+
+class EncryptedPdfError(GulagCleanerError):
+    """The PDF is encrypted and could not be decrypted without a password."""
+
+class UnsupportedLayoutError(GulagCleanerError):
+    """The cleaning method could not handle the layout of the PDF."""
+
+    method: str
+    page: Optional[int]
+
+class LimitExceededError(GulagCleanerError):
+    """The PDF needs more resources than the limits allow."""
+
+    limit: str
+
+class CleanOptions:
+    """Options that control how a PDF is cleaned."""
+
+    force_naive: bool
+    preview: bool
+    verify: bool
+    def __init__(
+        self, force_naive: bool = False, preview: bool = False, verify: bool = False
+    ) -> None: ...
+    def to_dict(self) -> dict[str, Any]: ...
+    def to_json(self) -> str: ...
+
+class PageReport:
+    """What was planned for a page of the original document."""
+
+    @property
+    def number(self) -> int: ...
+    @property
+    def page_type(self) -> str: ...
+    @property
+    def action(self) -> str: ...
+    @property
+    def deleted(self) -> bool: ...
+    @property
+    def logos(self) -> int: ...
+    @property
+    def incomplete(self) -> bool: ...
+    def to_dict(self) -> dict[str, Any]: ...
+    def to_json(self) -> str: ...
+
+class CleanReport:
+    """What was, or would be, done to a document while cleaning it."""
+
+    @property
+    def method(self) -> str: ...
+    @property
+    def method_code(self) -> int: ...
+    @property
+    def pages(self) -> list[PageReport]: ...
+    @property
+    def deleted_pages(self) -> int: ...
+    @property
+    def verification(self) -> Optional[dict[str, Any]]: ...
+    @property
+    def attempts(self) -> list[dict[str, Any]]: ...
+    @property
+    def diagnostics(self) -> Optional[list[Any]]: ...
+    def to_dict(self) -> dict[str, Any]: ...
+    def to_json(self) -> str: ...
+
+class Metadata:
+    """The details of a document printed on its cover page."""
+
+    @property
+    def file(self) -> Optional[str]: ...
+    @property
+    def author(self) -> Optional[str]: ...
+    @property
+    def subject(self) -> Optional[str]: ...
+    @property
+    def course(self) -> Optional[str]: ...
+    @property
+    def faculty(self) -> Optional[str]: ...
+    @property
+    def university(self) -> Optional[str]: ...
+    def to_dict(self) -> dict[str, Optional[str]]: ...
+    def to_json(self) -> str: ...
+
+def clean_pdf(data: Buffer, force_naive: bool) -> Tuple[bytes, int]: ...
+def clean_pdf_report(
+    data: Buffer, options: Optional[CleanOptions] = None
+) -> Tuple[bytes, CleanReport]: ...
+def detect_pdf(data: Buffer, options: Optional[CleanOptions] = None) -> CleanReport: ...
+def extract_metadata(data: Buffer) -> Metadata: ...
+def clean_pdf_file(input_path: str, output_path: str, force_naive: bool) -> int: ...
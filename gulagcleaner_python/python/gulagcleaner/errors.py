@@ -0,0 +1,8 @@
+# The exceptions raised by the binary extension, importable without it.
+from ._lib import (  # noqa: F401
+    GulagCleanerError,
+    InvalidPdfError,
+    EncryptedPdfError,
+    UnsupportedLayoutError,
+    LimitExceededError,
+)
@@ -1,13 +1,204 @@
+use pyo3::buffer::PyBuffer;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use pyo3::wrap_pyfunction;
 
+use gulagcleaner_rs::{CleanError, CleanOptions};
+
+/// Python classes wrapping the options and reports of the core.
+mod types;
+
+use types::{CleanReport, Metadata, PageReport};
+
+create_exception!(
+    gulagcleaner,
+    GulagCleanerError,
+    PyException,
+    "Base class of the errors raised while cleaning a PDF."
+);
+create_exception!(
+    gulagcleaner,
+    InvalidPdfError,
+    GulagCleanerError,
+    "The data could not be read or written as a PDF document."
+);
+create_exception!(
+    gulagcleaner,
+    EncryptedPdfError,
+    GulagCleanerError,
+    "The PDF is encrypted and could not be decrypted without a password."
+);
+create_exception!(
+    gulagcleaner,
+    UnsupportedLayoutError,
+    GulagCleanerError,
+    "The cleaning method could not handle the layout of the PDF. The `method` attribute holds \
+     the name of the method and `page` the page that failed, or None."
+);
+create_exception!(
+    gulagcleaner,
+    LimitExceededError,
+    GulagCleanerError,
+    "The PDF needs more resources than the limits allow. The `limit` attribute holds the name \
+     of the limit that was exceeded."
+);
+
+/// Converts an error of the core into the matching Python exception.
+fn to_py_err(e: CleanError) -> PyErr {
+    let message = e.to_string();
+    match e {
+        CleanError::InvalidPdf(_) => InvalidPdfError::new_err(message),
+        CleanError::Encrypted => EncryptedPdfError::new_err(message),
+        CleanError::Io(_) => PyIOError::new_err(message),
+        CleanError::UnsupportedLayout { method, page, .. } => {
+            let err = UnsupportedLayoutError::new_err(message);
+            Python::with_gil(|py| {
+                let value = err.value(py);
+                value
+                    .setattr("method", method)
+                    .and_then(|_| value.setattr("page", page))
+                    .err()
+                    .unwrap_or(err)
+            })
+        }
+        CleanError::LimitExceeded { limit, .. } => {
+            let err = LimitExceededError::new_err(message);
+            Python::with_gil(|py| {
+                err.value(py)
+                    .setattr("limit", limit.name())
+                    .err()
+                    .unwrap_or(err)
+            })
+        }
+        CleanError::Panicked(_) => GulagCleanerError::new_err(message),
+    }
+}
+
+/// Cleans a PDF given as any object supporting the buffer protocol, such as `bytes`,
+/// `bytearray`, `memoryview` or `mmap`. Returns the cleaned PDF as `bytes` and the code of the
+/// method used, and raises a `GulagCleanerError` if the PDF cannot be cleaned.
+///
+/// The GIL is released while the PDF is cleaned, so other Python threads keep running. The
+/// buffer must not be modified until the call returns.
+#[pyfunction]
+pub fn clean_pdf<'py>(
+    py: Python<'py>,
+    data: PyBuffer<u8>,
+    force_naive: bool,
+) -> PyResult<(Bound<'py, PyBytes>, u8)> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let (clean_pdf, report) = with_bytes(py, &data, |data| {
+        gulagcleaner_rs::clean_pdf_report(data, &options)
+    })?
+    .map_err(to_py_err)?;
+    Ok((PyBytes::new(py, &clean_pdf), report.method_code))
+}
+
+/// Cleans a PDF like `clean_pdf`, and returns the cleaned PDF as `bytes` with the report of
+/// what was done to it.
+#[pyfunction]
+#[pyo3(signature = (data, options = None))]
+pub fn clean_pdf_report<'py>(
+    py: Python<'py>,
+    data: PyBuffer<u8>,
+    options: Option<types::CleanOptions>,
+) -> PyResult<(Bound<'py, PyBytes>, CleanReport)> {
+    let options = options.map(|x| x.inner).unwrap_or_default();
+    let (clean_pdf, report) = with_bytes(py, &data, |data| {
+        gulagcleaner_rs::clean_pdf_report(data, &options)
+    })?
+    .map_err(to_py_err)?;
+    Ok((PyBytes::new(py, &clean_pdf), CleanReport { inner: report }))
+}
+
+/// Detects how a PDF would be cleaned, and what would be done to each page, without
+/// cleaning it.
+#[pyfunction]
+#[pyo3(signature = (data, options = None))]
+pub fn detect_pdf(
+    py: Python<'_>,
+    data: PyBuffer<u8>,
+    options: Option<types::CleanOptions>,
+) -> PyResult<CleanReport> {
+    let options = options.map(|x| x.inner).unwrap_or_default();
+    let report = with_bytes(py, &data, |data| {
+        gulagcleaner_rs::detect_pdf(data, &options)
+    })?
+    .map_err(to_py_err)?;
+    Ok(CleanReport { inner: report })
+}
+
+/// Reads the details printed on the cover page of a PDF.
+#[pyfunction]
+pub fn extract_metadata(py: Python<'_>, data: PyBuffer<u8>) -> PyResult<Metadata> {
+    let metadata =
+        with_bytes(py, &data, gulagcleaner_rs::metadata::extract_metadata)?.map_err(to_py_err)?;
+    Ok(Metadata { inner: metadata })
+}
+
+/// Runs `f` on the bytes of a buffer with the GIL released, without copying them when the
+/// buffer is contiguous.
+fn with_bytes<T, F>(py: Python<'_>, data: &PyBuffer<u8>, f: F) -> PyResult<T>
+where
+    T: Send,
+    F: FnOnce(&[u8]) -> T + Send,
+{
+    if data.is_c_contiguous() {
+        // SAFETY: the buffer is contiguous, holds `len_bytes` bytes, and stays alive and in
+        // place while `data` holds the export, which outlives the slice. Exporters such as
+        // `bytearray` refuse to be resized while exported.
+        let slice =
+            unsafe { std::slice::from_raw_parts(data.buf_ptr() as *const u8, data.len_bytes()) };
+        Ok(py.allow_threads(|| f(slice)))
+    } else {
+        let owned = data.to_vec(py)?;
+        Ok(py.allow_threads(|| f(&owned)))
+    }
+}
+
+/// Cleans the PDF file at `input_path` and saves it at `output_path`, which may be the same
+/// path. The output file is replaced atomically. Returns the code of the method used.
+/// The GIL is released while the file is cleaned.
 #[pyfunction]
-pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> PyResult<(Vec<u8>, u8)> {
-    let (clean_pdf, method_code) = gulagcleaner_rs::clean_pdf(data, force_naive);
-    Ok((clean_pdf, method_code))
+pub fn clean_pdf_file(
+    py: Python<'_>,
+    input_path: &str,
+    output_path: &str,
+    force_naive: bool,
+) -> PyResult<u8> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let report = py
+        .allow_threads(|| gulagcleaner_rs::clean_path(input_path, output_path, &options))
+        .map_err(to_py_err)?;
+    Ok(report.method_code)
 }
 
 #[pymodule]
 fn _lib(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(clean_pdf, m)?)
+    let py = m.py();
+    m.add("GulagCleanerError", py.get_type::<GulagCleanerError>())?;
+    m.add("InvalidPdfError", py.get_type::<InvalidPdfError>())?;
+    m.add("EncryptedPdfError", py.get_type::<EncryptedPdfError>())?;
+    m.add(
+        "UnsupportedLayoutError",
+        py.get_type::<UnsupportedLayoutError>(),
+    )?;
+    m.add("LimitExceededError", py.get_type::<LimitExceededError>())?;
+    m.add_class::<types::CleanOptions>()?;
+    m.add_class::<PageReport>()?;
+    m.add_class::<CleanReport>()?;
+    m.add_class::<Metadata>()?;
+    m.add_function(wrap_pyfunction!(clean_pdf, m)?)?;
+    m.add_function(wrap_pyfunction!(clean_pdf_report, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_pdf, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_metadata, m)?)?;
+    m.add_function(wrap_pyfunction!(clean_pdf_file, m)?)
 }
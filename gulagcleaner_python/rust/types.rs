@@ -0,0 +1,330 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde::Serialize;
+
+use gulagcleaner_rs::{
+    metadata::Metadata as RsMetadata, models::plan::PageAction, models::plan::PagePlan,
+    CleanOptions as RsCleanOptions, CleanReport as RsCleanReport,
+};
+
+/// Serializes a value of the core to a JSON string.
+fn to_json<T: Serialize>(value: &T) -> PyResult<String> {
+    serde_json::to_string(value).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+/// Converts a value of the core to plain Python objects, through its JSON form.
+fn to_python<'py, T: Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?.call_method1("loads", (to_json(value)?,))
+}
+
+/// Formats an optional string the way Python prints it.
+fn repr_str(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("{value:?}"),
+        None => "None".to_string(),
+    }
+}
+
+fn repr_bool(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// Options that control how a PDF is cleaned.
+#[pyclass(name = "CleanOptions", module = "gulagcleaner")]
+#[derive(Clone)]
+pub struct CleanOptions {
+    pub inner: RsCleanOptions,
+}
+
+#[pymethods]
+impl CleanOptions {
+    #[new]
+    #[pyo3(signature = (force_naive = false, preview = false, verify = false))]
+    fn new(force_naive: bool, preview: bool, verify: bool) -> Self {
+        CleanOptions {
+            inner: RsCleanOptions {
+                force_naive,
+                preview,
+                verify,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Whether to use the naive cleaning method regardless of the detected layout.
+    #[getter]
+    fn force_naive(&self) -> bool {
+        self.inner.force_naive
+    }
+
+    #[setter]
+    fn set_force_naive(&mut self, value: bool) {
+        self.inner.force_naive = value;
+    }
+
+    /// Whether to keep every page and outline what would be removed instead of removing it.
+    #[getter]
+    fn preview(&self) -> bool {
+        self.inner.preview
+    }
+
+    #[setter]
+    fn set_preview(&mut self, value: bool) {
+        self.inner.preview = value;
+    }
+
+    /// Whether to compare the cleaned document with the original to check no content was lost.
+    #[getter]
+    fn verify(&self) -> bool {
+        self.inner.verify
+    }
+
+    #[setter]
+    fn set_verify(&mut self, value: bool) {
+        self.inner.verify = value;
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CleanOptions(force_naive={}, preview={}, verify={})",
+            repr_bool(self.inner.force_naive),
+            repr_bool(self.inner.preview),
+            repr_bool(self.inner.verify)
+        )
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("force_naive", self.inner.force_naive)?;
+        dict.set_item("preview", self.inner.preview)?;
+        dict.set_item("verify", self.inner.verify)?;
+        Ok(dict)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&serde_json::json!({
+            "force_naive": self.inner.force_naive,
+            "preview": self.inner.preview,
+            "verify": self.inner.verify,
+        }))
+    }
+}
+
+/// What was planned for a page of the original document.
+#[pyclass(name = "PageReport", module = "gulagcleaner", frozen)]
+#[derive(Clone)]
+pub struct PageReport {
+    pub inner: PagePlan,
+}
+
+#[pymethods]
+impl PageReport {
+    /// The 1-based page number in the original document.
+    #[getter]
+    fn number(&self) -> u32 {
+        self.inner.number
+    }
+
+    /// How the page was classified, such as "BannerAds" or "FullPageAds".
+    #[getter]
+    fn page_type(&self) -> String {
+        format!("{:?}", self.inner.page_type)
+    }
+
+    /// A short description of what is done to the page, such as "crop" or "delete".
+    #[getter]
+    fn action(&self) -> String {
+        self.inner.action.describe()
+    }
+
+    /// Whether the page is removed from the document.
+    #[getter]
+    fn deleted(&self) -> bool {
+        self.inner.action == PageAction::Delete
+    }
+
+    /// The number of logo images hidden from the page.
+    #[getter]
+    fn logos(&self) -> usize {
+        self.inner.logos.len()
+    }
+
+    /// Whether the original content of the page is missing, so only a preview of it is left.
+    #[getter]
+    fn incomplete(&self) -> bool {
+        self.inner.incomplete
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PageReport(number={}, page_type={:?}, action={:?})",
+            self.inner.number,
+            self.page_type(),
+            self.action()
+        )
+    }
+
+    /// The full plan of the page, including the boxes and objects involved.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.inner)
+    }
+}
+
+/// What was, or would be, done to a document while cleaning it.
+#[pyclass(name = "CleanReport", module = "gulagcleaner", frozen)]
+#[derive(Clone)]
+pub struct CleanReport {
+    pub inner: RsCleanReport,
+}
+
+#[pymethods]
+impl CleanReport {
+    /// The name of the method: "Wuolah", "StuDocu", "Naive" or "Docsity".
+    #[getter]
+    fn method(&self) -> &'static str {
+        self.inner.method
+    }
+
+    /// The code of the method: 0 for "Wuolah", 1 for "StuDocu", 2 for "Naive", and 3 for "Docsity".
+    #[getter]
+    fn method_code(&self) -> u8 {
+        self.inner.method_code
+    }
+
+    /// What was planned for each page of the original document.
+    #[getter]
+    fn pages(&self) -> Vec<PageReport> {
+        self.inner
+            .pages
+            .iter()
+            .map(|plan| PageReport {
+                inner: plan.clone(),
+            })
+            .collect()
+    }
+
+    /// The number of pages removed from the document.
+    #[getter]
+    fn deleted_pages(&self) -> usize {
+        self.inner
+            .pages
+            .iter()
+            .filter(|plan| plan.action == PageAction::Delete)
+            .count()
+    }
+
+    /// The comparison of the cleaned document with the original as a dict, if it was asked for.
+    #[getter]
+    fn verification<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        self.inner
+            .verification
+            .as_ref()
+            .map(|verification| to_python(py, verification))
+            .transpose()
+    }
+
+    /// The methods tried while cleaning, as dicts with the `method` and the `rejection` that
+    /// explains why its result was not used, which is `None` for the method that was.
+    #[getter]
+    fn attempts<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner.attempts)
+    }
+
+    /// The problems found in the structure of the cleaned PDF, as dicts keyed by the kind of
+    /// problem, or `None` if the PDF was not validated.
+    #[getter]
+    fn diagnostics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner.diagnostics)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CleanReport(method={:?}, pages={}, deleted_pages={})",
+            self.inner.method,
+            self.inner.pages.len(),
+            self.deleted_pages()
+        )
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.inner)
+    }
+}
+
+/// The details of a document printed on its cover page. Any of them may be None.
+#[pyclass(name = "Metadata", module = "gulagcleaner", frozen, eq)]
+#[derive(Clone, PartialEq)]
+pub struct Metadata {
+    pub inner: RsMetadata,
+}
+
+#[pymethods]
+impl Metadata {
+    /// The name of the file that was uploaded.
+    #[getter]
+    fn file(&self) -> Option<String> {
+        self.inner.file.clone()
+    }
+
+    /// The user that uploaded the file.
+    #[getter]
+    fn author(&self) -> Option<String> {
+        self.inner.author.clone()
+    }
+
+    /// The subject the document is about.
+    #[getter]
+    fn subject(&self) -> Option<String> {
+        self.inner.subject.clone()
+    }
+
+    /// The course and degree of the subject.
+    #[getter]
+    fn course(&self) -> Option<String> {
+        self.inner.course.clone()
+    }
+
+    /// The faculty that teaches the subject.
+    #[getter]
+    fn faculty(&self) -> Option<String> {
+        self.inner.faculty.clone()
+    }
+
+    /// The university of the faculty.
+    #[getter]
+    fn university(&self) -> Option<String> {
+        self.inner.university.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Metadata(file={}, author={}, subject={}, course={}, faculty={}, university={})",
+            repr_str(&self.inner.file),
+            repr_str(&self.inner.author),
+            repr_str(&self.inner.subject),
+            repr_str(&self.inner.course),
+            repr_str(&self.inner.faculty),
+            repr_str(&self.inner.university)
+        )
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner)
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.inner)
+    }
+}
@@ -0,0 +1,25 @@
+#![no_main]
+
+use std::time::Duration;
+
+use gulagcleaner_rs::{clean_pdf_report, CleanOptions, Limits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the options, so that every method and mode is explored.
+    let Some((&flags, data)) = data.split_first() else {
+        return;
+    };
+    let options = CleanOptions {
+        force_naive: flags & 1 != 0,
+        preview: flags & 2 != 0,
+        verify: flags & 4 != 0,
+        limits: Limits {
+            max_decompressed_bytes: 64 * 1024 * 1024,
+            time_budget: Some(Duration::from_secs(10)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let _ = clean_pdf_report(data, &options);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use gulagcleaner_rs::{detect_pdf, CleanOptions, Limits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let options = CleanOptions {
+        limits: Limits {
+            max_decompressed_bytes: 64 * 1024 * 1024,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let _ = detect_pdf(data, &options);
+});
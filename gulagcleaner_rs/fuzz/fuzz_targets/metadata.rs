@@ -0,0 +1,8 @@
+#![no_main]
+
+use gulagcleaner_rs::metadata::extract_metadata;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = extract_metadata(data);
+});
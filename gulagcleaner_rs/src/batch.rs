@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    clean::{clean_pdf_report, CleanOptions, CleanReport},
+    error::{panic_message, CleanError},
+};
+
+#[cfg(feature = "fs")]
+use std::{fs, path::PathBuf};
+
+/// The result of cleaning one document of a batch.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileReport {
+    /// The size of the original document, in bytes.
+    pub input_bytes: u64,
+    /// The size of the cleaned document, in bytes.
+    pub output_bytes: u64,
+    /// What was done to the document.
+    pub report: CleanReport,
+}
+
+/// Totals over all the documents of a batch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BatchSummary {
+    /// The number of documents that were cleaned.
+    pub cleaned: usize,
+    /// The number of documents that could not be cleaned.
+    pub failed: usize,
+    /// How many documents were cleaned with each method, by method name.
+    pub methods: BTreeMap<&'static str, usize>,
+    /// The total size of the cleaned documents before cleaning, in bytes.
+    pub input_bytes: u64,
+    /// The total size of the cleaned documents after cleaning, in bytes.
+    pub output_bytes: u64,
+}
+
+impl BatchSummary {
+    /// Sums up the results of a batch.
+    pub fn new<'a>(results: impl IntoIterator<Item = &'a Result<FileReport, CleanError>>) -> Self {
+        let mut summary = BatchSummary::default();
+        for result in results {
+            match result {
+                Ok(file) => {
+                    summary.cleaned += 1;
+                    *summary.methods.entry(file.report.method).or_default() += 1;
+                    summary.input_bytes += file.input_bytes;
+                    summary.output_bytes += file.output_bytes;
+                }
+                Err(_) => summary.failed += 1,
+            }
+        }
+        summary
+    }
+
+    /// The number of bytes saved by cleaning, negative if the documents grew.
+    pub fn bytes_saved(&self) -> i64 {
+        self.input_bytes as i64 - self.output_bytes as i64
+    }
+}
+
+/// A document to clean as part of a batch, read from `input` and saved at `output`.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchJob {
+    /// The path of the PDF document.
+    pub input: PathBuf,
+    /// The path the cleaned PDF document is saved at, which may be the same as `input`.
+    pub output: PathBuf,
+}
+
+/// The results of cleaning a batch of documents.
+#[derive(Debug)]
+pub struct BatchReport<T> {
+    /// The result of every document, in the order they were given.
+    pub files: Vec<(T, Result<FileReport, CleanError>)>,
+    /// The totals over all the documents.
+    pub summary: BatchSummary,
+}
+
+/// Cleans many PDF documents in memory, on up to `concurrency` threads.
+///
+/// A document that fails to clean does not stop the others: every document gets its own result.
+///
+/// # Arguments
+///
+/// * `documents` - The PDF documents to clean.
+/// * `options` - The options used to detect and clean the ads of every document.
+/// * `concurrency` - The maximum number of documents cleaned at the same time, or 0 to use
+///   as many threads as the machine can run in parallel.
+///
+/// # Returns
+///
+/// The cleaned data or the error of each document, in the order they were given, along with
+/// a summary of the batch.
+pub fn clean_batch_bytes(
+    documents: Vec<Vec<u8>>,
+    options: &CleanOptions,
+    concurrency: usize,
+) -> BatchReport<Vec<u8>> {
+    let files = run_pool(documents, concurrency, |data| {
+        let (output, report) = clean_pdf_report(&data, options)?;
+        let file = FileReport {
+            input_bytes: data.len() as u64,
+            output_bytes: output.len() as u64,
+            report,
+        };
+        Ok((output, file))
+    });
+
+    let files: Vec<_> = files
+        .into_iter()
+        .map(|result| match result {
+            Ok((output, file)) => (output, Ok(file)),
+            Err(e) => (Vec::new(), Err(e)),
+        })
+        .collect();
+    let summary = BatchSummary::new(files.iter().map(|x| &x.1));
+    BatchReport { files, summary }
+}
+
+/// Cleans many PDF files, on up to `concurrency` threads.
+///
+/// Each file is cleaned like `clean_path` does, so every output is written atomically. A file
+/// that fails to clean does not stop the others: every job gets its own result.
+///
+/// # Arguments
+///
+/// * `jobs` - The files to clean and where to save them.
+/// * `options` - The options used to detect and clean the ads of every file.
+/// * `concurrency` - The maximum number of files cleaned at the same time, or 0 to use as many
+///   threads as the machine can run in parallel.
+///
+/// # Returns
+///
+/// The result of each job, in the order they were given, along with a summary of the batch.
+#[cfg(feature = "fs")]
+pub fn clean_batch(
+    jobs: Vec<BatchJob>,
+    options: &CleanOptions,
+    concurrency: usize,
+) -> BatchReport<BatchJob> {
+    let results = run_pool(jobs.clone(), concurrency, |job| {
+        let input_bytes = fs::metadata(&job.input)?.len();
+        let report = crate::io::clean_path(&job.input, &job.output, options)?;
+        Ok(FileReport {
+            input_bytes,
+            output_bytes: fs::metadata(&job.output)?.len(),
+            report,
+        })
+    });
+
+    let files: Vec<_> = jobs.into_iter().zip(results).collect();
+    let summary = BatchSummary::new(files.iter().map(|x| &x.1));
+    BatchReport { files, summary }
+}
+
+/// Runs `f` on every item, on at most `concurrency` threads, and returns the results in order.
+///
+/// A panic while handling an item is turned into an error for that item only.
+fn run_pool<T, R, F>(items: Vec<T>, concurrency: usize, f: F) -> Vec<Result<R, CleanError>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Result<R, CleanError> + Sync,
+{
+    let concurrency = match concurrency {
+        0 => thread::available_parallelism().map_or(1, |x| x.get()),
+        n => n,
+    }
+    .min(items.len())
+    .max(1);
+
+    let count = items.len();
+    let queue: Vec<Mutex<Option<T>>> = items.into_iter().map(|x| Mutex::new(Some(x))).collect();
+    let results: Vec<Mutex<Option<Result<R, CleanError>>>> =
+        (0..count).map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break;
+                }
+                let Some(item) = queue[index].lock().unwrap().take() else {
+                    continue;
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item)))
+                    .unwrap_or_else(|payload| Err(CleanError::Panicked(panic_message(payload))));
+                *results[index].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|x| x.into_inner().unwrap().expect("every item is handled"))
+        .collect()
+}
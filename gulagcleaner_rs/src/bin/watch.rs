@@ -0,0 +1,67 @@
+//! Watches folders and cleans every PDF that is dropped in them.
+//!
+//! Usage: gulagcleaner-watch [-o <output_dir>] [-j <journal>] [-n] <dir>...
+
+use std::{env, path::PathBuf, process, time::Duration};
+
+use gulagcleaner_rs::watch::{FolderWatcher, OutputLocation, WatchOptions};
+
+const USAGE: &str = "Usage: gulagcleaner-watch [-o <output_dir>] [-j <journal>] [-n] <dir>...
+
+Watches the directories and cleans every new PDF once it has been completely written.
+
+Optional arguments:
+  -o <output_dir>  Save the cleaned files in this directory instead of alongside the originals.
+  -j <journal>     Record the processed files in this file. Defaults to
+                   .gulagcleaner-journal in the first directory.
+  -n               Force the naive cleaning method.
+  -h               Show this help message.";
+
+fn main() {
+    let mut dirs = Vec::new();
+    let mut output = None;
+    let mut journal = None;
+    let mut force_naive = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            "-n" => force_naive = true,
+            "-o" => output = args.next().map(PathBuf::from),
+            "-j" => journal = args.next().map(PathBuf::from),
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+    if dirs.is_empty() {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    }
+
+    let mut options = WatchOptions::new(dirs);
+    if let Some(output) = output {
+        options.output = OutputLocation::Directory(output);
+    }
+    if let Some(journal) = journal {
+        options.journal = journal;
+    }
+    options.clean.force_naive = force_naive;
+
+    let result = FolderWatcher::new(options).and_then(|mut watcher| {
+        watcher.run(Duration::from_secs(60), |event| match &event.result {
+            Ok(method) => println!(
+                "Cleaned {} with {method}. File saved in {}",
+                event.input.display(),
+                event.output.display()
+            ),
+            Err(e) => eprintln!("Error cleaning {}: {e}", event.input.display()),
+        })
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
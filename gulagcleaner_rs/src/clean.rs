@@ -1,11 +1,94 @@
-use crate::models::method::Method;
+use crate::{
+    error::{panic_message, CleanError},
+    limits::{check_document_until, Deadline, Limits},
+    models::{
+        analysis::{delete_pages, DocumentAnalysis},
+        docsity::{footer_signatures, has_docsity_links},
+        method::{apply_plan, is_inserted_ad, Method},
+        plan::{PageAction, PagePlan},
+        signature::AdSignatures,
+    },
+    preview::render_preview,
+    validate::{validate_pdf, Diagnostic},
+    verify::{self, Verification},
+};
 
 use lopdf::Document;
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
+};
 
 /// Trait implemented by the different PDF methods
 pub trait Cleaner {
-    fn clean(&mut self, doc: &mut Document) -> (Vec<u32>, u8);
+    /// Decides what to do with each page of the document, which was analysed into `analysis`,
+    /// without modifying it.
+    fn plan(
+        &self,
+        doc: &Document,
+        analysis: &DocumentAnalysis,
+        options: &CleanOptions,
+    ) -> Vec<PagePlan>;
+}
+
+/// Options that control how a PDF document is cleaned.
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    /// Whether to use the naive cleaning method regardless of the detected layout.
+    pub force_naive: bool,
+    /// The ad signatures used to classify pages and find logos.
+    pub signatures: AdSignatures,
+    /// Whether to keep every page and outline what would be removed instead of removing it.
+    pub preview: bool,
+    /// Whether to compare the cleaned document with the original to check no content was lost.
+    pub verify: bool,
+    /// The resources a document may use while it is detected and cleaned.
+    pub limits: Limits,
+    /// Whether to load the saved document again and check its structure.
+    pub validate: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            force_naive: false,
+            signatures: AdSignatures::builtin().clone(),
+            preview: false,
+            verify: false,
+            limits: Limits::default(),
+            validate: true,
+        }
+    }
+}
+
+/// What was done to a document while cleaning it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CleanReport {
+    /// The name of the method used.
+    pub method: &'static str,
+    /// The code of the method used: 0 for "Wuolah", 1 for "StuDocu", 2 for "Naive", and 3 for
+    /// "Docsity".
+    pub method_code: u8,
+    /// What was planned for each page of the original document.
+    pub pages: Vec<PagePlan>,
+    /// The comparison of the cleaned document with the original, if it was asked for.
+    pub verification: Option<Verification>,
+    /// The methods that were tried, in order, ending with the one that was used. Empty if the
+    /// document was only detected.
+    pub attempts: Vec<MethodAttempt>,
+    /// The problems found in the structure of the saved document, if it was validated.
+    pub diagnostics: Option<Vec<Diagnostic>>,
+}
+
+/// A method that was tried while cleaning a document.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MethodAttempt {
+    /// The name of the method.
+    pub method: &'static str,
+    /// Why the document the method produced was not used, or `None` if it was.
+    pub rejection: Option<String>,
 }
 
 /// Cleans a PDF document by modifying its pages and removing unnecessary content.
@@ -17,80 +100,308 @@ pub trait Cleaner {
 ///
 /// # Returns
 ///
-/// A tuple containing the cleaned PDF document data as a vector of bytes and a method code.
+/// A tuple containing the cleaned PDF document data as a vector of bytes and a method code, or
+/// the error that prevented the document from being cleaned.
+///
+/// The method code indicates the cleaning method used: 0 for "Wuolah", 1 for "StuDocu", 2 for "Naive", and 3 for "Docsity".
+pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> Result<(Vec<u8>, u8), CleanError> {
+    clean_pdf_with_options(
+        data,
+        &CleanOptions {
+            force_naive,
+            ..Default::default()
+        },
+    )
+}
+
+/// Cleans a PDF document like `clean_pdf`, with full control over the cleaning options.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data as a vector of bytes.
+/// * `options` - The options used to detect and clean the ads.
+///
+/// # Returns
 ///
-/// The method code indicates the cleaning method used: 0 for "Wuolah", 1 for "StuDocu", and 2 for "Naive".
-pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> (Vec<u8>, u8) {
+/// A tuple containing the cleaned PDF document data as a vector of bytes and a method code, or
+/// the error that prevented the document from being cleaned.
+pub fn clean_pdf_with_options(
+    data: Vec<u8>,
+    options: &CleanOptions,
+) -> Result<(Vec<u8>, u8), CleanError> {
+    let (return_stream, report) = clean_pdf_report(&data, options)?;
+    Ok((return_stream, report.method_code))
+}
 
+/// Cleans a PDF document and reports what was done to it.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data.
+/// * `options` - The options used to detect and clean the ads.
+///
+/// # Returns
+///
+/// A tuple containing the cleaned PDF document data as a vector of bytes and the report of
+/// the cleaning, or the error that prevented the document from being cleaned.
+pub fn clean_pdf_report(
+    data: &[u8],
+    options: &CleanOptions,
+) -> Result<(Vec<u8>, CleanReport), CleanError> {
     //Load the PDF into a Document
-    let mut doc = Document::load_mem(&data).unwrap();
+    let mut doc = Document::load_mem(data)?;
 
-    //We first need to determine what method we're using, either "Wuolah", "StuDocu" or "Wuolah naive".
-    // We keep it like this to allow for future methods if needed.
+    let mut report = clean_document(&mut doc, options)?;
+
+    //Save the document.
+    let mut return_stream = Vec::new();
+    doc.save_to(&mut return_stream)?;
+    if options.validate {
+        report.diagnostics = Some(validate_pdf(&return_stream, &report));
+    }
+    Ok((return_stream, report))
+}
+
+/// Cleans a loaded PDF document in place.
+///
+/// # Arguments
+///
+/// * `doc` - The document to clean.
+/// * `options` - The options used to detect and clean the ads.
+///
+/// # Returns
+///
+/// The report of the cleaning, or the error that prevented the document from being cleaned.
+pub fn clean_document(
+    doc: &mut Document,
+    options: &CleanOptions,
+) -> Result<CleanReport, CleanError> {
+    let deadline = Deadline::start(&options.limits);
+    decrypt(doc)?;
+    // The document is saved as a whole, so the offsets of the previous revisions are stale.
+    doc.trailer.remove(b"Prev");
+    doc.trailer.remove(b"XRefStm");
+    check_document_until(doc, &options.limits, &deadline)?;
+    let (method, analysis, mut report) = detect(doc, options, &deadline)?;
+
+    if options.preview {
+        render_preview(doc, &method, &report.pages, &analysis.budget).map_err(|e| {
+            CleanError::UnsupportedLayout {
+                method: method.name(),
+                page: None,
+                reason: format!("could not draw the preview: {e}"),
+            }
+        })?;
+        analysis.budget.check("drawing the preview")?;
+        return Ok(report);
+    }
+
+    // The detected method cleans a copy of the document first, so that if it fails or leaves
+    // pages blank the naive method can start over from the original.
+    let mut candidates = vec![method];
+    if !matches!(candidates[0], Method::Naive) {
+        candidates.push(Method::Naive);
+    }
+    let mut last_error = None;
+    for (i, method) in candidates.into_iter().enumerate() {
+        let plans = match i {
+            0 => std::mem::take(&mut report.pages),
+            _ => method.plan(doc, &analysis, options),
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            try_method(doc, &analysis, &method, &plans)
+        }))
+        .unwrap_or_else(|payload| Err(CleanError::Panicked(panic_message(payload))));
+        analysis.budget.check("cleaning the pages")?;
+
+        let cleaned = match result {
+            Ok(cleaned) => cleaned,
+            Err(e @ CleanError::LimitExceeded { .. }) => return Err(e),
+            Err(e) => {
+                report.attempts.push(MethodAttempt {
+                    method: method.name(),
+                    rejection: Some(e.to_string()),
+                });
+                last_error = Some(e);
+                continue;
+            }
+        };
+        report.attempts.push(MethodAttempt {
+            method: method.name(),
+            rejection: None,
+        });
+        let before = options
+            .verify
+            .then(|| verify::snapshot(doc, &plans, &options.signatures, &analysis.budget));
+        *doc = cleaned;
+        report.method = method.name();
+        report.method_code = method.code();
+        report.pages = plans;
+        report.verification = before.map(|before| {
+            verify::compare(
+                &before,
+                doc,
+                &report.pages,
+                &options.signatures,
+                &analysis.budget,
+            )
+        });
+        analysis.budget.check("verifying the output")?;
+        return Ok(report);
+    }
+    Err(last_error.unwrap_or(CleanError::UnsupportedLayout {
+        method: report.method,
+        page: None,
+        reason: "no method could clean the document".to_string(),
+    }))
+}
 
-    //Each method should mark pages for deletion in to_delete and modify the contents of the pages.
+/// Cleans a copy of a document with a method, and rejects the result if a page that showed
+/// something is left blank.
+fn try_method(
+    doc: &Document,
+    analysis: &DocumentAnalysis,
+    method: &Method,
+    plans: &[PagePlan],
+) -> Result<Document, CleanError> {
+    let mut cleaned = doc.clone();
 
-    let (to_delete, method_code) = match_method(&doc, force_naive).clean(&mut doc);
+    //Each plan marks its page for deletion or modifies its contents.
+    apply_plan(&mut cleaned, analysis, plans).map_err(|(page, e)| {
+        CleanError::UnsupportedLayout {
+            method: method.name(),
+            page: Some(page),
+            reason: e.to_string(),
+        }
+    })?;
 
     //Delete the pages that we've marked for deletion.
-    for (offset, page) in to_delete.into_iter().enumerate() {
-        doc.delete_pages(&[page - offset as u32]);
+    let to_delete: Vec<_> = plans
+        .iter()
+        .filter(|plan| plan.action == PageAction::Delete)
+        .map(|plan| plan.id)
+        .collect();
+    delete_pages(&mut cleaned, &to_delete);
+
+    let kept = plans
+        .iter()
+        .filter(|plan| plan.action != PageAction::Delete);
+    for (plan, (_, id)) in kept.zip(cleaned.get_pages()) {
+        if verify::is_blank(&cleaned, &id) && !verify::is_blank(doc, &plan.id) {
+            return Err(CleanError::UnsupportedLayout {
+                method: method.name(),
+                page: Some(plan.number),
+                reason: "the page is blank after cleaning".to_string(),
+            });
+        }
     }
-    //Save the document.
-    let mut return_stream = Vec::new();
-    doc.save_to(&mut return_stream).unwrap();
+    Ok(cleaned)
+}
 
-    // Should we still return the method_code now that we are going multi-language? I will leave it not returned for now.
-    //return_stream.push(method_code);
-    (return_stream, method_code)
-    //doc.save_to("test.pdf").unwrap();
+/// Detects the method a PDF document would be cleaned with and what would be done to each page,
+/// without cleaning it.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data.
+/// * `options` - The options used to detect the ads.
+///
+/// # Returns
+///
+/// The report of the cleaning that would be done, or the error that prevented the document
+/// from being read.
+pub fn detect_pdf(data: &[u8], options: &CleanOptions) -> Result<CleanReport, CleanError> {
+    let mut doc = Document::load_mem(data)?;
+    decrypt(&mut doc)?;
+    detect_document(&doc, options)
 }
 
-/// Creates a new `Method` instance based on the provided `Document` and `force_naive` flag.
+/// Detects what would be done to a loaded PDF document, like `detect_pdf`.
+pub fn detect_document(doc: &Document, options: &CleanOptions) -> Result<CleanReport, CleanError> {
+    let deadline = Deadline::start(&options.limits);
+    check_document_until(doc, &options.limits, &deadline)?;
+    let (_, _, report) = detect(doc, options, &deadline)?;
+    Ok(report)
+}
+
+/// Decrypts an encrypted document in place, which works when it only has an owner password,
+/// as is the case for documents that can be opened but not edited.
+pub(crate) fn decrypt(doc: &mut Document) -> Result<(), CleanError> {
+    if !doc.is_encrypted() {
+        return Ok(());
+    }
+    doc.decrypt("").map_err(|_| CleanError::Encrypted)?;
+    // The document is saved unencrypted, so the encryption dictionary no longer applies.
+    doc.trailer.remove(b"Encrypt");
+    Ok(())
+}
+
+fn detect(
+    doc: &Document,
+    options: &CleanOptions,
+    deadline: &Deadline,
+) -> Result<(Method, DocumentAnalysis, CleanReport), CleanError> {
+    let analysis =
+        DocumentAnalysis::new_until(doc, &options.signatures, &options.limits, deadline)?;
+
+    //We first need to determine what method we're using, either "Wuolah", "StuDocu", "Docsity" or "Wuolah naive".
+    // We keep it like this to allow for future methods if needed.
+    let method = match_method(doc, &analysis, options.force_naive);
+    let plans = method.plan(doc, &analysis, options);
+    analysis.budget.check("detecting the ads")?;
+
+    let report = CleanReport {
+        method: method.name(),
+        method_code: method.code(),
+        pages: plans,
+        verification: None,
+        attempts: vec![],
+        diagnostics: None,
+    };
+    Ok((method, analysis, report))
+}
+
+/// Creates a new `Method` instance based on the analysis of a document and the `force_naive` flag.
 ///
 /// # Arguments
 ///
-/// * `doc` - A reference to the `Document` object.
+/// * `doc` - The document that was analysed.
+/// * `analysis` - The analysis of every page of the document.
 /// * `force_naive` - A boolean flag indicating whether to force the use of the naive method.
 ///
 /// # Returns
 ///
-/// A `Method` instance representing the chosen method based on the provided `Document` and `force_naive` flag.
-fn match_method(doc: &Document, force_naive: bool) -> Method {
+/// A `Method` instance representing the chosen method based on the provided analysis and `force_naive` flag.
+fn match_method(doc: &Document, analysis: &DocumentAnalysis, force_naive: bool) -> Method {
     //0 for auto, 1 for wuolah, 2 for studocu 3 for wuolah naive
     if force_naive {
         return Method::Naive;
     }
 
-    let pages = doc.get_pages();
-    let content_list: Vec<Vec<(u32, u16)>> = pages
-        .iter()
-        .map(|x| doc.get_page_contents(*x.1))
-        .filter(|x| x.len() > 1)
-        .collect();
-    let to_delete: Vec<u32> = pages
-        .iter()
-        .filter(|x| {
-            let contents = doc.get_page_contents(*x.1);
+    let pages = &analysis.pages;
 
-            contents.len() < 4
-        })
-        .map(|x| *x.0)
-        .collect();
-
-    if content_list
-        .iter()
-        .map(|x| x.len())
-        .filter(|x| *x == 3)
-        .collect::<Vec<_>>()
-        .len()
-        == pages.len()
-    {
-        return Method::StuDocu(content_list);
+    // StuDocu stamps the pages it serves, but the uploader may have added pages of their own, so
+    // the document only needs half of its pages stamped. Stops reading pages once it is decided.
+    let needed = pages.len().div_ceil(2).max(1);
+    let mut stamped = 0;
+    for (i, page) in pages.iter().enumerate() {
+        if stamped >= needed || stamped + (pages.len() - i) < needed {
+            break;
+        }
+        if page.studocu(doc, &analysis.budget).is_some() {
+            stamped += 1;
+        }
+    }
+    if stamped >= needed {
+        return Method::StuDocu;
+    }
+    // Docsity links every page it serves, and as with StuDocu, half of them is enough.
+    if pages.iter().filter(|page| has_docsity_links(page)).count() >= needed {
+        return Method::Docsity(footer_signatures(doc, pages, &analysis.budget));
     }
     let long_content_list: Vec<Vec<(u32, u16)>> = pages
         .iter()
-        .map(|x| doc.get_page_contents(*x.1))
+        .map(|x| x.contents.clone())
         .filter(|x| x.len() > 3)
         .collect();
 
@@ -103,6 +414,11 @@ fn match_method(doc: &Document, force_naive: bool) -> Method {
             .len()
             > 1
     {
+        let to_delete: Vec<u32> = pages
+            .iter()
+            .filter(|x| is_inserted_ad(doc, x, &analysis.budget))
+            .map(|x| x.number)
+            .collect();
         return Method::Wuolah(long_content_list, to_delete);
     }
     Method::Naive
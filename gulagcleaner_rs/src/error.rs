@@ -0,0 +1,97 @@
+use std::{error::Error, fmt};
+
+use crate::limits::Limit;
+
+/// The errors that can happen while cleaning a PDF document.
+#[derive(Debug)]
+pub enum CleanError {
+    /// The data could not be read or written as a PDF document.
+    InvalidPdf(lopdf::Error),
+    /// The document is encrypted and could not be decrypted without a password.
+    Encrypted,
+    /// Reading the input or writing the output failed.
+    Io(std::io::Error),
+    /// The method could not handle the layout of the document.
+    UnsupportedLayout {
+        /// The name of the method that failed.
+        method: &'static str,
+        /// The page that could not be cleaned, if the failure is specific to one.
+        page: Option<u32>,
+        /// A description of what went wrong.
+        reason: String,
+    },
+    /// The document needs more resources than the limits allow.
+    LimitExceeded {
+        /// The limit that was exceeded.
+        limit: Limit,
+        /// A description of what exceeded it.
+        detail: String,
+    },
+    /// Cleaning panicked, which is a bug in the cleaner. Holds the panic message.
+    Panicked(String),
+}
+
+impl fmt::Display for CleanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CleanError::InvalidPdf(e) => write!(f, "invalid PDF: {e}"),
+            CleanError::Io(e) => write!(f, "I/O error: {e}"),
+            CleanError::Encrypted => {
+                write!(
+                    f,
+                    "the PDF is encrypted and cannot be opened without a password"
+                )
+            }
+            CleanError::UnsupportedLayout {
+                method,
+                page: Some(page),
+                reason,
+            } => write!(f, "the {method} method failed on page {page}: {reason}"),
+            CleanError::UnsupportedLayout {
+                method,
+                page: None,
+                reason,
+            } => write!(f, "the {method} method failed: {reason}"),
+            CleanError::LimitExceeded { limit, detail } => {
+                write!(f, "the PDF exceeds the {limit} limit: {detail}")
+            }
+            CleanError::Panicked(message) => write!(f, "cleaning panicked: {message}"),
+        }
+    }
+}
+
+impl Error for CleanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CleanError::InvalidPdf(e) => Some(e),
+            CleanError::Io(e) => Some(e),
+            CleanError::Encrypted
+            | CleanError::UnsupportedLayout { .. }
+            | CleanError::LimitExceeded { .. }
+            | CleanError::Panicked(_) => None,
+        }
+    }
+}
+
+impl From<lopdf::Error> for CleanError {
+    fn from(e: lopdf::Error) -> Self {
+        CleanError::InvalidPdf(e)
+    }
+}
+
+impl From<std::io::Error> for CleanError {
+    fn from(e: std::io::Error) -> Self {
+        CleanError::Io(e)
+    }
+}
+
+/// The message of a caught panic.
+pub(crate) fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
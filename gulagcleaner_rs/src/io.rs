@@ -0,0 +1,112 @@
+use std::io::{Read, Write};
+
+use lopdf::Document;
+
+use crate::{
+    clean::{clean_document, CleanOptions, CleanReport},
+    error::CleanError,
+};
+
+#[cfg(feature = "fs")]
+use crate::validate::validate_pdf;
+#[cfg(feature = "fs")]
+use std::{fs, io::BufWriter, path::Path};
+
+/// Cleans a PDF document read from `input` and writes the result to `output`.
+///
+/// The input is only buffered while the document is parsed, and the output is written
+/// directly to `output` instead of being collected in memory first. As the output cannot be
+/// read back, the document is not validated, whatever `options.validate` says.
+///
+/// # Arguments
+///
+/// * `input` - Where the PDF document is read from.
+/// * `output` - Where the cleaned PDF document is written to.
+/// * `options` - The options used to detect and clean the ads.
+///
+/// # Returns
+///
+/// The report of the cleaning, or the error that prevented the document from being cleaned.
+pub fn clean_reader<R: Read, W: Write>(
+    input: R,
+    output: W,
+    options: &CleanOptions,
+) -> Result<CleanReport, CleanError> {
+    let mut doc = Document::load_from(input)?;
+    let report = clean_document(&mut doc, options)?;
+    save_to(&mut doc, output)?;
+    Ok(report)
+}
+
+/// Cleans the PDF document at `input` and saves the result at `output`.
+///
+/// The cleaned document is first written to a temporary file next to `output`, which is then
+/// renamed over it. `output` is therefore either left untouched or completely replaced, even
+/// if cleaning fails halfway, and it can be the same path as `input`.
+///
+/// # Arguments
+///
+/// * `input` - The path of the PDF document.
+/// * `output` - The path the cleaned PDF document is saved at.
+/// * `options` - The options used to detect and clean the ads.
+///
+/// # Returns
+///
+/// The report of the cleaning, or the error that prevented the document from being cleaned.
+#[cfg(feature = "fs")]
+pub fn clean_path(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &CleanOptions,
+) -> Result<CleanReport, CleanError> {
+    let mut doc = load_path(input.as_ref())?;
+    let mut report = clean_document(&mut doc, options)?;
+    save_path(&mut doc, output.as_ref())?;
+    if options.validate {
+        report.diagnostics = Some(validate_pdf(&fs::read(output.as_ref())?, &report));
+    }
+    Ok(report)
+}
+
+/// Loads the PDF document at `path`, memory-mapping it when the `mmap` feature is enabled.
+#[cfg(feature = "fs")]
+pub fn load_path(path: &Path) -> Result<Document, CleanError> {
+    #[cfg(feature = "mmap")]
+    {
+        let file = fs::File::open(path)?;
+        // SAFETY: the map is only read while the document is parsed, and dropped right after.
+        // If another process truncates the file in the meantime, reading it may fault, which
+        // is the usual caveat of memory-mapped files.
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            return Ok(Document::load_mem(&map)?);
+        }
+    }
+
+    Ok(Document::load(path)?)
+}
+
+/// Saves a PDF document at `path` atomically, through a temporary file in the same directory.
+///
+/// If `path` already exists, the new file gets the same permissions.
+#[cfg(feature = "fs")]
+pub fn save_path(doc: &mut Document, path: &Path) -> Result<(), CleanError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+
+    save_to(doc, BufWriter::new(temp.as_file()))?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+fn save_to<W: Write>(doc: &mut Document, mut output: W) -> Result<(), CleanError> {
+    doc.save_to(&mut output)?;
+    output.flush()?;
+    Ok(())
+}
@@ -12,19 +12,55 @@
     ```rust
     use gulagcleaner_rs::clean::clean_pdf;
 
-    fn main(){
-        let data = std::fs::read("example_docs/wuolah-free-example.pdf").unwrap();
-        let (clean_pdf, _) = clean_pdf(data, false);
-        //Stores the clean pdf in the out directory
-        std::fs::write("example_docs/out/wuolah_clean.pdf", clean_pdf).unwrap();
-    }
+    let data = std::fs::read("example_docs/wuolah-170924-example.pdf").unwrap();
+    let (clean_pdf, _) = clean_pdf(data, false).unwrap();
+    //Stores the clean pdf in the out directory
+    std::fs::create_dir_all("example_docs/out").unwrap();
+    std::fs::write("example_docs/out/wuolah_clean.pdf", clean_pdf).unwrap();
     ```
 */
 /// Main method execution
 pub mod clean;
 
+/// Preview of the cleaning, drawn over the original document
+pub mod preview;
+
+/// Verification that cleaning did not lose real content
+pub mod verify;
+
+/// Structural checks of the saved documents
+pub mod validate;
+
+/// Errors returned while cleaning
+pub mod error;
+
+/// Limits on the resources a document may use while it is cleaned
+pub mod limits;
+
+/// Details printed on the cover page of the documents
+pub mod metadata;
+
+/// Streaming and path based entry points
+pub mod io;
+
+/// Cleaning many documents at once on a thread pool
+pub mod batch;
+
+/// Watching folders and cleaning the PDFs dropped in them
+#[cfg(feature = "watch")]
+pub mod watch;
+
 /// Main method rexport
-pub use clean::clean_pdf;
+pub use clean::{
+    clean_pdf, clean_pdf_report, clean_pdf_with_options, detect_pdf, CleanOptions, CleanReport,
+    MethodAttempt,
+};
+pub use error::CleanError;
+pub use validate::Diagnostic;
+pub use limits::Limits;
+#[cfg(feature = "fs")]
+pub use io::clean_path;
+pub use io::clean_reader;
 
 /// Modeling the different pdf sources and types
 pub mod models {
@@ -33,6 +69,27 @@ pub mod models {
 
     /// Represents the different page types used in the Gulag Cleaner application.
     pub mod page_type;
+
+    /// Traversal of page resources, including inherited ones and nested Form XObjects.
+    pub mod resources;
+
+    /// Fingerprints used to recognise ad images by size and content.
+    pub mod signature;
+
+    /// Page boxes and image placement.
+    pub mod geometry;
+
+    /// The per-page decisions taken by a method before it modifies a document.
+    pub mod plan;
+
+    /// What is read from each page once, and shared by detection, planning and cleaning.
+    pub mod analysis;
+
+    /// The streams StuDocu adds to the pages it serves.
+    pub mod studocu;
+
+    /// The banners and promo pages Docsity adds to its downloads.
+    pub mod docsity;
 }
 
 #[cfg(test)]
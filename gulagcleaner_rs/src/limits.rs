@@ -0,0 +1,511 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, Write},
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use flate2::read::ZlibDecoder;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use weezl::BitOrder;
+
+use crate::error::CleanError;
+
+/// Form XObjects nested deeper than this are never descended into, whatever the limits, so that
+/// walking them cannot overflow the stack.
+pub(crate) const MAX_NESTING: usize = 256;
+
+/// A walk of the content of a page stops after placing this many images, whatever the limits,
+/// so that forms drawn many times over cannot fill the memory with their placements.
+pub(crate) const MAX_PLACEMENTS: usize = 4096;
+
+/// The resources a document may use while it is detected and cleaned.
+///
+/// The defaults are far above what any real download needs, and only stop documents built to
+/// exhaust the memory or time of the process cleaning them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// The maximum number of bytes the streams of the document may decompress to, in total.
+    pub max_decompressed_bytes: u64,
+    /// The maximum number of objects in the document.
+    pub max_objects: usize,
+    /// The maximum depth of the page tree and of the Form XObjects nested in each other.
+    pub max_depth: usize,
+    /// The maximum number of operations read while walking the content of the pages and the
+    /// forms they draw, in total.
+    pub max_operations: u64,
+    /// How long detecting and cleaning a document may take, once it is parsed. Ignored on
+    /// WebAssembly, which has no clock to measure it with.
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_decompressed_bytes: 512 * 1024 * 1024,
+            max_objects: 1_000_000,
+            max_depth: 64,
+            max_operations: 100_000_000,
+            time_budget: None,
+        }
+    }
+}
+
+/// A limit that a document exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    DecompressedBytes,
+    Objects,
+    Depth,
+    Operations,
+    Time,
+}
+
+impl Limit {
+    /// The name of the limit, as used in error messages and by the bindings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limit::DecompressedBytes => "max_decompressed_bytes",
+            Limit::Objects => "max_objects",
+            Limit::Depth => "max_depth",
+            Limit::Operations => "max_operations",
+            Limit::Time => "time_budget",
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn exceeded(limit: Limit, detail: String) -> CleanError {
+    CleanError::LimitExceeded { limit, detail }
+}
+
+/// The point in time by which a document must be done, if there is a time budget.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline {
+    #[cfg(not(target_arch = "wasm32"))]
+    end: Option<Instant>,
+}
+
+impl Deadline {
+    pub(crate) fn start(limits: &Limits) -> Deadline {
+        #[cfg(target_arch = "wasm32")]
+        let _ = limits;
+        Deadline {
+            #[cfg(not(target_arch = "wasm32"))]
+            end: limits
+                .time_budget
+                .and_then(|budget| Instant::now().checked_add(budget)),
+        }
+    }
+
+    /// Whether the time budget ran out.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn is_over(&self) -> bool {
+        self.end.is_some_and(|end| Instant::now() > end)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn is_over(&self) -> bool {
+        false
+    }
+
+    /// Fails if the time budget ran out before or during `stage`.
+    pub(crate) fn check(&self, stage: &str) -> Result<(), CleanError> {
+        if self.is_over() {
+            return Err(exceeded(
+                Limit::Time,
+                format!("the time budget ran out while {stage}"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What is left of the limits of a document while the content of its pages is walked.
+///
+/// Every walk of the same document spends from the same budget, so that pages drawing the same
+/// forms over and over cannot add up to more work than the limits allow. A walk that runs out
+/// stops where it is, and `check` reports the limit afterwards.
+#[derive(Debug)]
+pub struct Budget {
+    deadline: Deadline,
+    max_operations: u64,
+    operations: Cell<u64>,
+    exceeded: Cell<Option<Limit>>,
+}
+
+impl Budget {
+    /// A budget for walking a document with `limits`, whose time starts running now.
+    pub fn new(limits: &Limits) -> Budget {
+        Budget::until(limits, Deadline::start(limits))
+    }
+
+    pub(crate) fn until(limits: &Limits, deadline: Deadline) -> Budget {
+        Budget {
+            deadline,
+            max_operations: limits.max_operations,
+            operations: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    /// Spends `count` operations, returning `false` once the operations or the time ran out.
+    pub(crate) fn spend(&self, count: u64) -> bool {
+        if self.is_spent() {
+            return false;
+        }
+        let operations = self.operations.get().saturating_add(count);
+        self.operations.set(operations);
+        if operations > self.max_operations {
+            self.exceeded.set(Some(Limit::Operations));
+        } else if self.deadline.is_over() {
+            self.exceeded.set(Some(Limit::Time));
+        }
+        self.exceeded.get().is_none()
+    }
+
+    /// Whether a walk ran out of operations or of time.
+    pub(crate) fn is_spent(&self) -> bool {
+        self.exceeded.get().is_some()
+    }
+
+    /// Fails if a walk ran out of operations, or the time budget ran out before or during
+    /// `stage`.
+    pub(crate) fn check(&self, stage: &str) -> Result<(), CleanError> {
+        if self.exceeded.get() == Some(Limit::Operations) {
+            return Err(exceeded(
+                Limit::Operations,
+                format!(
+                    "the pages draw more than {} operations",
+                    self.max_operations
+                ),
+            ));
+        }
+        self.deadline.check(stage)
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::new(&Limits::default())
+    }
+}
+
+/// Checks a parsed document against the limits before anything else is done with it.
+///
+/// Every stream is decoded through its filter chain to measure it, stopping as soon as the total
+/// goes over the limit, and the last filter only counts what it would decode, so a compression
+/// bomb is never held in memory. Image codecs and unknown filters count their encoded size.
+pub fn check_document(doc: &Document, limits: &Limits) -> Result<(), CleanError> {
+    check_document_until(doc, limits, &Deadline::start(limits))
+}
+
+pub(crate) fn check_document_until(
+    doc: &Document,
+    limits: &Limits,
+    deadline: &Deadline,
+) -> Result<(), CleanError> {
+    if doc.objects.len() > limits.max_objects {
+        return Err(exceeded(
+            Limit::Objects,
+            format!(
+                "the document has {} objects, more than {}",
+                doc.objects.len(),
+                limits.max_objects
+            ),
+        ));
+    }
+
+    let depth = page_tree_depth(doc);
+    if depth > limits.max_depth {
+        return Err(exceeded(
+            Limit::Depth,
+            format!(
+                "the page tree is more than {} levels deep",
+                limits.max_depth
+            ),
+        ));
+    }
+    let depth = form_depth(doc);
+    if depth > limits.max_depth {
+        return Err(exceeded(
+            Limit::Depth,
+            format!(
+                "Form XObjects are nested more than {} levels deep",
+                limits.max_depth
+            ),
+        ));
+    }
+
+    let mut total: u64 = 0;
+    for object in doc.objects.values() {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        let budget = limits.max_decompressed_bytes.saturating_sub(total);
+        total += decompressed_size(&stream.dict, &stream.content, budget);
+        if total > limits.max_decompressed_bytes {
+            return Err(exceeded(
+                Limit::DecompressedBytes,
+                format!(
+                    "the streams decompress to more than {} bytes",
+                    limits.max_decompressed_bytes
+                ),
+            ));
+        }
+        deadline.check("checking the document")?;
+    }
+    Ok(())
+}
+
+/// The size a stream decompresses to, counting at most `budget + 1` bytes.
+///
+/// The filters are undone in the order they are listed. Image codecs and unknown filters stop
+/// the chain, and the stream counts the size it had before them.
+fn decompressed_size(dict: &Dictionary, content: &[u8], budget: u64) -> u64 {
+    let filters: Vec<&[u8]> = match dict.get(b"Filter") {
+        Ok(Object::Name(name)) => vec![name],
+        Ok(Object::Array(filters)) => filters.iter().filter_map(|x| x.as_name().ok()).collect(),
+        _ => vec![],
+    };
+    let limit = budget.saturating_add(1);
+    let mut data = Cow::Borrowed(content);
+    for (i, filter) in filters.iter().enumerate() {
+        let early_change = early_change(dict, i);
+        // The last stage is only counted, so a compression bomb is never held in memory.
+        if i + 1 == filters.len() {
+            let mut output = Capped::new(io::sink(), limit);
+            if decode(filter, &data, early_change, &mut output) {
+                return output.written;
+            }
+            break;
+        }
+        let mut output = Capped::new(Vec::new(), limit);
+        if !decode(filter, &data, early_change, &mut output) {
+            break;
+        }
+        data = Cow::Owned(output.inner);
+    }
+    data.len() as u64
+}
+
+/// Undoes `filter` on `input`, writing what it decodes to `output`, or returns `false` if the
+/// filter is not one that compresses. Streams that fail halfway keep what was decoded until then.
+fn decode(filter: &[u8], input: &[u8], early_change: bool, output: &mut impl Write) -> bool {
+    let _ = match filter {
+        b"FlateDecode" => io::copy(&mut ZlibDecoder::new(input), output).map(|_| ()),
+        b"LZWDecode" => {
+            let mut decoder = match early_change {
+                true => weezl::decode::Decoder::with_tiff_size_switch(BitOrder::Msb, 8),
+                false => weezl::decode::Decoder::new(BitOrder::Msb, 8),
+            };
+            decoder.into_stream(output).decode_all(input).status
+        }
+        b"ASCII85Decode" => output.write_all(&decode_ascii85(input)),
+        b"ASCIIHexDecode" => output.write_all(&decode_ascii_hex(input)),
+        _ => return false,
+    };
+    true
+}
+
+/// The `/EarlyChange` parameter of the filter at `index`, which LZW streams default to.
+fn early_change(dict: &Dictionary, index: usize) -> bool {
+    let params = match dict.get(b"DecodeParms") {
+        Ok(Object::Array(params)) => params.get(index),
+        params => params.ok(),
+    };
+    params
+        .and_then(|x| x.as_dict().ok())
+        .and_then(|x| x.get(b"EarlyChange").ok())
+        .and_then(|x| x.as_i64().ok())
+        .is_none_or(|x| x != 0)
+}
+
+fn decode_ascii85(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 5 * 4 + 4);
+    let mut group = [0u8; 5];
+    let mut len = 0;
+    for &c in input.iter().filter(|x| !x.is_ascii_whitespace()) {
+        if c == b'z' && len == 0 {
+            output.extend_from_slice(&[0; 4]);
+            continue;
+        }
+        if !(b'!'..=b'u').contains(&c) {
+            break;
+        }
+        group[len] = c - b'!';
+        len += 1;
+        if len == 5 {
+            output.extend_from_slice(&ascii85_group(&group));
+            len = 0;
+        }
+    }
+    if len > 1 {
+        group[len..].fill(b'u' - b'!');
+        output.extend_from_slice(&ascii85_group(&group)[..len - 1]);
+    }
+    output
+}
+
+fn ascii85_group(group: &[u8; 5]) -> [u8; 4] {
+    let value = group.iter().fold(0u32, |value, &x| {
+        value.wrapping_mul(85).wrapping_add(x as u32)
+    });
+    value.to_be_bytes()
+}
+
+fn decode_ascii_hex(input: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = input
+        .iter()
+        .filter(|x| !x.is_ascii_whitespace())
+        .take_while(|x| **x != b'>')
+        .map_while(|x| (*x as char).to_digit(16).map(|x| x as u8))
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// A writer that keeps at most `limit` bytes and fails once it is full, so that decoders stop.
+struct Capped<W> {
+    inner: W,
+    written: u64,
+    limit: u64,
+}
+
+impl<W> Capped<W> {
+    fn new(inner: W, limit: u64) -> Self {
+        Capped {
+            inner,
+            written: 0,
+            limit,
+        }
+    }
+}
+
+impl<W: Write> Write for Capped<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.limit - self.written;
+        if room == 0 && !buf.is_empty() {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        let len = (buf.len() as u64).min(room) as usize;
+        let written = self.inner.write(&buf[..len])?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The number of levels of the page tree, walked without recursion.
+fn page_tree_depth(doc: &Document) -> usize {
+    let Ok(root) = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+    else {
+        return 0;
+    };
+    let mut seen = HashSet::from([root]);
+    let mut level = vec![root];
+    let mut depth = 0;
+    while !level.is_empty() {
+        depth += 1;
+        level = level
+            .iter()
+            .filter_map(|id| {
+                doc.get_dictionary(*id)
+                    .ok()?
+                    .get(b"Kids")
+                    .ok()?
+                    .as_array()
+                    .ok()
+            })
+            .flatten()
+            .filter_map(|kid| kid.as_reference().ok())
+            .filter(|kid| seen.insert(*kid))
+            .collect();
+    }
+    depth
+}
+
+/// The length of the longest chain of Form XObjects listed in each other's resources.
+///
+/// Forms that contain themselves are only counted once along a chain, like when they are drawn.
+fn form_depth(doc: &Document) -> usize {
+    let inner_forms = |id: &ObjectId| -> Vec<ObjectId> {
+        let Ok(stream) = doc.get_object(*id).and_then(Object::as_stream) else {
+            return vec![];
+        };
+        stream
+            .dict
+            .get(b"Resources")
+            .and_then(|x| doc.dereference(x))
+            .and_then(|x| x.1.as_dict())
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(|x| doc.dereference(x))
+            .and_then(|x| x.1.as_dict())
+            .map(|xobjs| {
+                xobjs
+                    .iter()
+                    .filter_map(|(_, x)| x.as_reference().ok())
+                    .filter(|x| is_form(doc, x))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    // Depth-first, with an explicit stack, memoizing the depth of each finished form.
+    let mut depths: HashMap<ObjectId, usize> = HashMap::new();
+    let mut on_stack = HashSet::new();
+    let mut deepest = 0;
+    for start in doc.objects.keys().filter(|id| is_form(doc, id)) {
+        if depths.contains_key(start) {
+            continue;
+        }
+        let mut stack = vec![(*start, inner_forms(start), 0)];
+        on_stack.insert(*start);
+        while let Some((id, children, next)) = stack.last_mut() {
+            if let Some(child) = children.get(*next).copied() {
+                *next += 1;
+                if !depths.contains_key(&child) && on_stack.insert(child) {
+                    let grandchildren = inner_forms(&child);
+                    stack.push((child, grandchildren, 0));
+                }
+                continue;
+            }
+            let depth = 1 + children
+                .iter()
+                .filter_map(|child| depths.get(child))
+                .max()
+                .unwrap_or(&0);
+            deepest = deepest.max(depth);
+            depths.insert(*id, depth);
+            on_stack.remove(id);
+            stack.pop();
+        }
+    }
+    deepest
+}
+
+fn is_form(doc: &Document, id: &ObjectId) -> bool {
+    doc.get_object(*id)
+        .and_then(Object::as_stream)
+        .and_then(|stream| stream.dict.get(b"Subtype"))
+        .and_then(Object::as_name)
+        .is_ok_and(|subtype| subtype == b"Form")
+}
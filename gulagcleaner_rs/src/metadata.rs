@@ -0,0 +1,164 @@
+use std::{collections::BTreeMap, error::Error};
+
+use lopdf::{content::Content, Document, Encoding, Object, ObjectId};
+
+use crate::{
+    clean::decrypt,
+    error::CleanError,
+    limits::{check_document, Limits},
+    models::geometry::as_number,
+};
+
+/// The first line of the copyright notice that follows the details on the cover of the
+/// documents downloaded from Wuolah.
+const COPYRIGHT_NOTICE: &str = "Reservados todos los derechos";
+
+/// The details of a document printed on its cover page.
+///
+/// Documents downloaded from Wuolah list the file name, the author, the subject, the course
+/// and degree, the faculty and the university, one per line. Recent downloads only show the
+/// first two, so every field may be missing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Metadata {
+    /// The name of the file that was uploaded.
+    pub file: Option<String>,
+    /// The user that uploaded the file.
+    pub author: Option<String>,
+    /// The subject the document is about.
+    pub subject: Option<String>,
+    /// The course and degree of the subject.
+    pub course: Option<String>,
+    /// The faculty that teaches the subject.
+    pub faculty: Option<String>,
+    /// The university of the faculty.
+    pub university: Option<String>,
+}
+
+/// Reads the details printed on the cover page of a PDF document.
+///
+/// # Arguments
+///
+/// * `data` - The PDF document data.
+///
+/// # Returns
+///
+/// The details that could be read, or the error that prevented the document from being read.
+pub fn extract_metadata(data: &[u8]) -> Result<Metadata, CleanError> {
+    let mut doc = Document::load_mem(data)?;
+    decrypt(&mut doc)?;
+    check_document(&doc, &Limits::default())?;
+    Ok(document_metadata(&doc))
+}
+
+/// Reads the details printed on the cover page of a loaded PDF document.
+///
+/// Documents without a cover, which is recognised by its copyright notice, have no details.
+pub fn document_metadata(doc: &Document) -> Metadata {
+    let Some(first_page) = doc.get_pages().get(&1).copied() else {
+        return Metadata::default();
+    };
+    let mut lines = page_text_lines(doc, first_page).unwrap_or_default();
+    let Some(notice) = lines
+        .iter()
+        .position(|line| line.starts_with(COPYRIGHT_NOTICE))
+    else {
+        return Metadata::default();
+    };
+    lines.truncate(notice);
+    let mut lines = lines.into_iter();
+
+    Metadata {
+        file: lines.next(),
+        author: lines.next(),
+        subject: lines.next(),
+        course: lines.next(),
+        faculty: lines.next(),
+        university: lines.next(),
+    }
+}
+
+/// Extracts the text of a page as the lines it is laid out in, skipping the empty ones.
+///
+/// A new line starts whenever the text moves down, which is how the cover pages lay out their
+/// details even when they are drawn in a single text object.
+pub fn page_text_lines(doc: &Document, page: ObjectId) -> Result<Vec<String>, Box<dyn Error>> {
+    let encodings: BTreeMap<Vec<u8>, Encoding> = doc
+        .get_page_fonts(page)?
+        .into_iter()
+        // lopdf asserts that the encoding is only read from fonts.
+        .filter(|(_, font)| font.type_is(b"Font"))
+        .filter_map(|(name, font)| Some((name, font.get_font_encoding(doc).ok()?)))
+        .collect();
+    let content = Content::decode(&doc.get_page_content(page)?)?;
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut encoding = None;
+    let mut line_y = None;
+    for operation in &content.operations {
+        let new_line = match operation.operator.as_str() {
+            "Tf" => {
+                encoding = operation
+                    .operands
+                    .first()
+                    .and_then(|x| x.as_name().ok())
+                    .and_then(|name| encodings.get(name));
+                false
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                // The quote operators move to the next line before showing their text.
+                if matches!(operation.operator.as_str(), "'" | "\"") {
+                    push_line(&mut lines, &mut line);
+                }
+                if let Some(encoding) = encoding {
+                    collect_text(&mut line, encoding, &operation.operands);
+                }
+                false
+            }
+            "Td" | "TD" => operation
+                .operands
+                .get(1)
+                .and_then(|x| as_number(x).ok())
+                .is_some_and(|ty| ty != 0.0),
+            "Tm" => {
+                let y = operation.operands.get(5).and_then(|x| as_number(x).ok());
+                let moved = y != line_y;
+                line_y = y;
+                moved
+            }
+            "T*" | "ET" => true,
+            _ => false,
+        };
+        if new_line {
+            push_line(&mut lines, &mut line);
+        }
+    }
+    push_line(&mut lines, &mut line);
+    Ok(lines)
+}
+
+fn push_line(lines: &mut Vec<String>, line: &mut String) {
+    let trimmed = line.trim();
+    if !trimmed.is_empty() {
+        lines.push(trimmed.to_string());
+    }
+    line.clear();
+}
+
+fn collect_text(text: &mut String, encoding: &Encoding, operands: &[Object]) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => {
+                if let Ok(decoded) = Document::decode_text(encoding, bytes) {
+                    text.push_str(&decoded);
+                }
+            }
+            Object::Array(array) => collect_text(text, encoding, array),
+            // A big negative adjustment in a TJ array is how words are spaced apart.
+            Object::Integer(x) if *x < -100 => text.push(' '),
+            Object::Real(x) if *x < -100.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
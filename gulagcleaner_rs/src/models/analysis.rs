@@ -0,0 +1,241 @@
+use std::{cell::OnceCell, collections::HashSet, error::Error};
+
+use lopdf::{Document, Object, ObjectId};
+
+use super::{
+    geometry::{as_number, media_box, Rect},
+    page_type::PageType,
+    resources::{collect_page_images, PageImage},
+    signature::AdSignatures,
+    studocu::{studocu_layout, StuDocuLayout},
+};
+use crate::{
+    error::CleanError,
+    limits::{Budget, Deadline, Limits},
+};
+
+#[cfg(test)]
+thread_local! {
+    /// How many pages were analysed on this thread, so tests can check the work done per page.
+    pub(crate) static ANALYSED_PAGES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    /// How many page contents were decoded on this thread.
+    pub(crate) static DECODED_CONTENTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// A link annotation of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// The rectangle of the annotation, if it has a valid one.
+    pub rect: Option<Rect>,
+    /// The URI the link opens, if it opens one.
+    pub uri: Option<String>,
+}
+
+/// Reads the rectangle and the URI of every annotation of a page.
+pub fn page_links(doc: &Document, page: &ObjectId) -> Result<Vec<Link>, Box<dyn Error>> {
+    let links = doc
+        .get_page_annotations(*page)?
+        .into_iter()
+        .map(|annot| {
+            let rect = annot
+                .get(b"Rect")
+                .and_then(|x| doc.dereference(x))
+                .and_then(|x| x.1.as_array())
+                .ok()
+                .and_then(|x| {
+                    let numbers: Vec<f32> = x.iter().filter_map(|x| as_number(x).ok()).collect();
+                    numbers.try_into().ok()
+                });
+            let uri = annot
+                .get(b"A")
+                .and_then(|x| doc.dereference(x))
+                .and_then(|x| x.1.as_dict())
+                .and_then(|x| x.get(b"URI"))
+                .and_then(|x| doc.dereference(x))
+                .and_then(|x| x.1.as_string())
+                .ok()
+                .map(|x| x.into_owned());
+            Link { rect, uri }
+        })
+        .collect();
+    Ok(links)
+}
+
+/// Everything the methods need to know about a page, read from the document once.
+///
+/// Detecting the method, planning and cleaning all work from the same analysis, so that every
+/// page is only walked once however many times it is looked at.
+#[derive(Debug)]
+pub struct PageAnalysis {
+    /// The 1-based page number.
+    pub number: u32,
+    /// The object id of the page dictionary.
+    pub id: ObjectId,
+    /// The content streams of the page, in the order they are drawn.
+    pub contents: Vec<ObjectId>,
+    /// The images the page can draw, including the ones nested in Form XObjects.
+    pub images: Vec<PageImage>,
+    /// The link annotations of the page.
+    pub links: Vec<Link>,
+    /// The media box of the page, if it has a valid one.
+    pub media_box: Option<Rect>,
+    /// How the page is classified.
+    pub page_type: PageType,
+    /// The decoded content of the page, read the first time it is needed.
+    content: OnceCell<Vec<u8>>,
+    /// How StuDocu laid out the streams of the page, read the first time it is needed.
+    studocu: OnceCell<Option<StuDocuLayout>>,
+}
+
+impl PageAnalysis {
+    /// Analyses a single page.
+    pub fn new(doc: &Document, number: u32, id: ObjectId, signatures: &AdSignatures) -> Self {
+        let images = collect_page_images(doc, &id).unwrap_or_default();
+        let links = page_links(doc, &id).unwrap_or_default();
+        let page_type = PageType::classify(doc, &images, &links, signatures);
+        #[cfg(test)]
+        ANALYSED_PAGES.with(|x| x.set(x.get() + 1));
+        PageAnalysis {
+            number,
+            id,
+            contents: doc.get_page_contents(id),
+            images,
+            links,
+            media_box: media_box(doc, &id).ok(),
+            page_type,
+            content: OnceCell::new(),
+            studocu: OnceCell::new(),
+        }
+    }
+
+    /// The content streams of the page decoded and joined together, as they were when the
+    /// page was analysed.
+    pub fn content(&self, doc: &Document) -> &[u8] {
+        self.content.get_or_init(|| {
+            #[cfg(test)]
+            DECODED_CONTENTS.with(|x| x.set(x.get() + 1));
+            doc.get_page_content(self.id).unwrap_or_default()
+        })
+    }
+
+    /// Which streams of the page StuDocu added, or `None` if the page does not print the StuDocu
+    /// identifier.
+    pub fn studocu(&self, doc: &Document, budget: &Budget) -> Option<&StuDocuLayout> {
+        self.studocu
+            .get_or_init(|| studocu_layout(doc, &self.id, &self.contents, budget))
+            .as_ref()
+    }
+}
+
+/// The analysis of every page of a document.
+#[derive(Debug, Default)]
+pub struct DocumentAnalysis {
+    /// The pages, in order.
+    pub pages: Vec<PageAnalysis>,
+    /// What is left of the limits for walking the content of the pages.
+    pub budget: Budget,
+}
+
+impl DocumentAnalysis {
+    /// Analyses every page of a document.
+    pub fn new(doc: &Document, signatures: &AdSignatures) -> Self {
+        let pages = doc
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| PageAnalysis::new(doc, number, id, signatures))
+            .collect();
+        DocumentAnalysis {
+            pages,
+            budget: Budget::default(),
+        }
+    }
+
+    /// Analyses every page of a document, failing as soon as the time budget runs out.
+    pub(crate) fn new_until(
+        doc: &Document,
+        signatures: &AdSignatures,
+        limits: &Limits,
+        deadline: &Deadline,
+    ) -> Result<Self, CleanError> {
+        let mut pages = Vec::new();
+        for (number, id) in doc.get_pages() {
+            deadline.check("analysing the pages")?;
+            pages.push(PageAnalysis::new(doc, number, id, signatures));
+        }
+        Ok(DocumentAnalysis {
+            pages,
+            budget: Budget::until(limits, *deadline),
+        })
+    }
+
+    /// The analysis of a page, by its 1-based number.
+    pub fn page(&self, number: u32) -> Option<&PageAnalysis> {
+        // Pages are numbered in order from 1, so this is usually a direct index.
+        let index = (number as usize).checked_sub(1)?;
+        match self.pages.get(index) {
+            Some(page) if page.number == number => Some(page),
+            _ => self.pages.iter().find(|page| page.number == number),
+        }
+    }
+}
+
+/// Removes pages from a document, with a single pass over its objects.
+///
+/// The pages are taken out of the page tree, the counts of their ancestors are updated, and
+/// every other reference to them is removed, like lopdf's `delete_pages` does one page at a
+/// time.
+pub fn delete_pages(doc: &mut Document, pages: &[ObjectId]) {
+    let deleted: HashSet<ObjectId> = pages.iter().copied().collect();
+
+    for page in &deleted {
+        let mut parent = doc
+            .get_dictionary(*page)
+            .and_then(|x| x.get(b"Parent"))
+            .and_then(Object::as_reference);
+        let mut seen = HashSet::new();
+        while let Ok(id) = parent {
+            if !seen.insert(id) {
+                break;
+            }
+            let Ok(node) = doc.get_dictionary_mut(id) else {
+                break;
+            };
+            if let Ok(count) = node.get(b"Count").and_then(Object::as_i64) {
+                node.set("Count", count - 1);
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference);
+        }
+    }
+    for page in &deleted {
+        doc.objects.remove(page);
+    }
+
+    let is_deleted = |x: &Object| matches!(x, Object::Reference(id) if deleted.contains(id));
+    let mut trailer = Object::Dictionary(std::mem::take(&mut doc.trailer));
+    let mut stack: Vec<&mut Object> = doc.objects.values_mut().collect();
+    stack.push(&mut trailer);
+    while let Some(object) = stack.pop() {
+        let dict = match object {
+            Object::Array(array) => {
+                array.retain(|x| !is_deleted(x));
+                stack.extend(array.iter_mut());
+                continue;
+            }
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        let keys: Vec<Vec<u8>> = dict
+            .iter()
+            .filter(|(_, x)| is_deleted(x))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            dict.remove(&key);
+        }
+        stack.extend(dict.iter_mut().map(|(_, x)| x));
+    }
+    if let Object::Dictionary(dict) = trailer {
+        doc.trailer = dict;
+    }
+}
@@ -0,0 +1,157 @@
+use lopdf::{Document, ObjectId};
+
+use super::{
+    analysis::{Link, PageAnalysis},
+    geometry::{content_image_placements, covers, Rect},
+    signature::AdSignature,
+};
+use crate::limits::Budget;
+
+/// The host of the links Docsity adds to the pages it serves, looked for in lowercase.
+pub const DOCSITY_HOST: &str = "docsity.com";
+
+/// The share of the page height, from the bottom, where the footer banners are drawn.
+const FOOTER_BAND: f32 = 0.12;
+
+/// The share of the page width a footer banner spans at least.
+const FOOTER_WIDTH: f32 = 0.5;
+
+/// The share of a footer banner its link covers at least.
+const FOOTER_LINK_COVERAGE: f32 = 0.5;
+
+/// The share of the page a link must cover to be taken for the link of a promo page. Footer
+/// links cover at most the footer band.
+const PROMO_LINK_COVERAGE: f32 = 0.5;
+
+/// The share of the page an image must cover to be taken for a branded cover or promo page.
+const PROMO_IMAGE_COVERAGE: f32 = 0.9;
+
+/// What Docsity added to a page it served.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocsityLayout {
+    /// The footer banner images drawn along the bottom of the page.
+    pub footers: Vec<ObjectId>,
+    /// The page box without the footer band, if the page has a footer banner.
+    pub crop: Option<Rect>,
+    /// Whether the page is the Docsity cover or a promo page, which have nothing of the
+    /// original document.
+    pub promo: bool,
+}
+
+/// Whether a link opens a Docsity page.
+pub fn is_docsity_link(link: &Link) -> bool {
+    link.uri
+        .as_deref()
+        .is_some_and(|uri| uri.to_ascii_lowercase().contains(DOCSITY_HOST))
+}
+
+/// Whether a page has any link to Docsity.
+pub fn has_docsity_links(page: &PageAnalysis) -> bool {
+    page.links.iter().any(is_docsity_link)
+}
+
+/// Works out what Docsity added to a page, from its links and where its images are drawn.
+///
+/// Footer banners are the images drawn across the bottom of the page under a Docsity link, or
+/// matching one of `footers`, which finds them on pages whose links were removed.
+///
+/// Returns `None` for pages without Docsity links or footer banners, like pages the uploader
+/// added to the document after it was downloaded.
+pub fn docsity_layout(
+    doc: &Document,
+    page: &PageAnalysis,
+    footers: &[AdSignature],
+    budget: &Budget,
+) -> Option<DocsityLayout> {
+    let links: Vec<Rect> = page
+        .links
+        .iter()
+        .filter(|link| is_docsity_link(link))
+        .map(|link| link.rect.unwrap_or_default())
+        .collect();
+    if links.is_empty() && footers.is_empty() {
+        return None;
+    }
+    let page_box = page.media_box?;
+    let [x0, y0, x1, y1] = page_box;
+    let placements =
+        content_image_placements(doc, &page.id, page.content(doc), budget).unwrap_or_default();
+
+    let is_footer_banner = |id: &ObjectId, rect: &Rect| {
+        let in_band = rect[3] <= y0 + FOOTER_BAND * (y1 - y0);
+        let wide = rect[2] - rect[0] >= FOOTER_WIDTH * (x1 - x0);
+        let linked = links
+            .iter()
+            .any(|link| covers(link, rect, FOOTER_LINK_COVERAGE));
+        let known = || {
+            page.images
+                .iter()
+                .filter(|image| image.id == *id)
+                .any(|image| {
+                    footers
+                        .iter()
+                        .any(|signature| signature.matches(doc, image))
+                })
+        };
+        in_band && wide && (linked || known())
+    };
+    let mut banners: Vec<&Rect> = Vec::new();
+    let mut footer_ids: Vec<ObjectId> = Vec::new();
+    for placement in &placements {
+        if is_footer_banner(&placement.id, &placement.rect) {
+            banners.push(&placement.rect);
+            if !footer_ids.contains(&placement.id) {
+                footer_ids.push(placement.id);
+            }
+        }
+    }
+    if links.is_empty() && footer_ids.is_empty() {
+        return None;
+    }
+
+    // Scanned pages also draw an image over the whole page, but keep their footer banner.
+    let promo = links
+        .iter()
+        .any(|link| covers(link, &page_box, PROMO_LINK_COVERAGE))
+        || (footer_ids.is_empty()
+            && placements
+                .iter()
+                .any(|placement| covers(&placement.rect, &page_box, PROMO_IMAGE_COVERAGE)));
+    let crop = banners
+        .iter()
+        .map(|rect| rect[3])
+        .reduce(f32::max)
+        .map(|top| [x0, top.max(y0), x1, y1]);
+
+    Some(DocsityLayout {
+        footers: footer_ids,
+        crop,
+        promo,
+    })
+}
+
+/// The signatures of the footer banners drawn under a Docsity link, used to find the same
+/// banners on the pages whose links were removed.
+pub fn footer_signatures(
+    doc: &Document,
+    pages: &[PageAnalysis],
+    budget: &Budget,
+) -> Vec<AdSignature> {
+    let mut signatures: Vec<AdSignature> = Vec::new();
+    for page in pages.iter().filter(|page| has_docsity_links(page)) {
+        let Some(layout) = docsity_layout(doc, page, &[], budget) else {
+            continue;
+        };
+        for image in page
+            .images
+            .iter()
+            .filter(|x| layout.footers.contains(&x.id))
+        {
+            let signature = AdSignature::from_image(doc, image);
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+    }
+    signatures
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId, Stream};
+
+use super::resources::page_resources;
+use crate::limits::{Budget, MAX_NESTING, MAX_PLACEMENTS};
+
+/// A rectangle in PDF user space, as `[llx, lly, urx, ury]`.
+pub type Rect = [f32; 4];
+
+/// The page boxes that are rewritten whenever a page is cropped.
+pub const PAGE_BOXES: [&str; 5] = ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"];
+
+/// Reads a number that may be stored either as an integer or as a real.
+pub fn as_number(obj: &Object) -> Result<f32, Box<dyn Error>> {
+    match obj.as_f32() {
+        Ok(x) => Ok(x),
+        _ => Ok(obj.as_i64()? as f32),
+    }
+}
+
+/// Returns the media box of a page, following the page tree if it is inherited.
+pub fn media_box(doc: &Document, page: &ObjectId) -> Result<Rect, Box<dyn Error>> {
+    let mut seen = HashSet::new();
+    let mut node_id = *page;
+
+    while seen.insert(node_id) {
+        let node = doc.get_dictionary(node_id)?;
+        if let Ok(mediabox) = node.get(b"MediaBox") {
+            let mediabox = doc.dereference(mediabox)?.1.as_array()?;
+            if mediabox.len() != 4 {
+                return Err("MediaBox does not have four numbers".into());
+            }
+            return Ok([
+                as_number(&mediabox[0])?,
+                as_number(&mediabox[1])?,
+                as_number(&mediabox[2])?,
+                as_number(&mediabox[3])?,
+            ]);
+        }
+        node_id = node.get(b"Parent")?.as_reference()?;
+    }
+
+    Err("Page has no MediaBox".into())
+}
+
+/// Sets every page box of a page dictionary to the same rectangle.
+pub fn set_page_boxes(page: &mut Dictionary, rect: Rect) {
+    for _box in PAGE_BOXES {
+        page.set(
+            _box,
+            Object::Array(rect.iter().map(|x| Object::Real(*x)).collect()),
+        );
+    }
+}
+
+/// Whether `rect` covers at least `share` of the area of `page_box`.
+pub fn covers(rect: &Rect, page_box: &Rect, share: f32) -> bool {
+    let area = |[llx, lly, urx, ury]: [f32; 4]| (urx - llx).max(0.0) * (ury - lly).max(0.0);
+    let overlap = [
+        rect[0].max(page_box[0]),
+        rect[1].max(page_box[1]),
+        rect[2].min(page_box[2]),
+        rect[3].min(page_box[3]),
+    ];
+    let page_area = area(*page_box);
+    page_area > 0.0 && area(overlap) >= share * page_area
+}
+
+/// Where an image is drawn on a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePlacement {
+    /// The object id of the image stream.
+    pub id: ObjectId,
+    /// The bounding box of the image on the page, in default user space.
+    pub rect: Rect,
+}
+
+/// A 2D affine transformation, as the six numbers of a PDF `cm` operator.
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Returns `m` followed by `n`, like `m n cm` would leave the CTM.
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+fn as_matrix(operands: &[Object]) -> Option<Matrix> {
+    let numbers: Vec<f32> = operands.iter().filter_map(|x| as_number(x).ok()).collect();
+    numbers.try_into().ok()
+}
+
+/// The bounding box of the unit square once transformed by `ctm`.
+fn unit_square_bounds(ctm: &Matrix) -> Rect {
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+        (
+            ctm[0] * x + ctm[2] * y + ctm[4],
+            ctm[1] * x + ctm[3] * y + ctm[5],
+        )
+    });
+    corners.iter().fold(
+        [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+        |[llx, lly, urx, ury], &(x, y)| [llx.min(x), lly.min(y), urx.max(x), ury.max(y)],
+    )
+}
+
+/// Finds where every image drawn by a page ends up, following Form XObjects.
+///
+/// Only the `q`, `Q`, `cm` and `Do` operators are interpreted, which is enough to place images.
+/// Forms nested deeper than `MAX_NESTING` are not followed, and the walk stops once it runs out
+/// of `budget` or has placed `MAX_PLACEMENTS` images.
+pub fn image_placements(
+    doc: &Document,
+    page: &ObjectId,
+    budget: &Budget,
+) -> Result<Vec<ImagePlacement>, Box<dyn Error>> {
+    let content = doc.get_page_content(*page)?;
+    content_image_placements(doc, page, &content, budget)
+}
+
+/// Finds where every image drawn by `content` ends up, when it is drawn on its own by a page.
+///
+/// This places the images of a single content stream of the page, which is only exact for
+/// streams that do not rely on the graphics state left by the streams before them.
+pub fn content_image_placements(
+    doc: &Document,
+    page: &ObjectId,
+    content: &[u8],
+    budget: &Budget,
+) -> Result<Vec<ImagePlacement>, Box<dyn Error>> {
+    let resources: Vec<&Dictionary> = page_resources(doc, page)?.into_iter().collect();
+    let mut walk = Walk {
+        doc,
+        budget,
+        forms: Vec::new(),
+        placed: HashMap::new(),
+    };
+    let placements = walk
+        .content(content, &resources, None)
+        .into_iter()
+        .map(|(id, ctm)| ImagePlacement {
+            id,
+            rect: unit_square_bounds(&ctm),
+        })
+        .collect();
+    Ok(placements)
+}
+
+/// Looks a name up in the `/XObject` dictionaries of a resource chain, nearest first.
+fn find_xobject(doc: &Document, resources: &[&Dictionary], name: &[u8]) -> Option<ObjectId> {
+    resources.iter().find_map(|dict| {
+        let xobjs = doc.dereference(dict.get(b"XObject").ok()?).ok()?.1;
+        xobjs.as_dict().ok()?.get(name).ok()?.as_reference().ok()
+    })
+}
+
+/// The images a content stream places, with the matrix that maps the unit square onto each of
+/// them in the space of the stream.
+type Placed = Vec<(ObjectId, Matrix)>;
+
+/// A walk of the content of a page and of the forms it draws.
+struct Walk<'a> {
+    doc: &'a Document,
+    budget: &'a Budget,
+    /// The forms being walked, from the outermost, so that forms drawing themselves are skipped.
+    forms: Vec<ObjectId>,
+    /// What each form places in its own space, by the form and by the form whose resources it
+    /// inherits, if it has none of its own. Forms drawn many times are only walked once.
+    placed: HashMap<(ObjectId, Option<ObjectId>), Placed>,
+}
+
+impl Walk<'_> {
+    /// Places the images drawn by `content`, whose resources are those of `owner`, or of the
+    /// page if it is `None`.
+    fn content(
+        &mut self,
+        content: &[u8],
+        resources: &[&Dictionary],
+        owner: Option<ObjectId>,
+    ) -> Placed {
+        let mut placed = Vec::new();
+        let Ok(content) = Content::decode(content) else {
+            return placed;
+        };
+        if !self.budget.spend(content.operations.len() as u64) {
+            return placed;
+        }
+        let mut ctm = IDENTITY;
+        let mut stack = Vec::new();
+
+        for operation in content.operations {
+            if placed.len() >= MAX_PLACEMENTS {
+                break;
+            }
+            match operation.operator.as_str() {
+                "q" => stack.push(ctm),
+                "Q" => ctm = stack.pop().unwrap_or(IDENTITY),
+                "cm" => {
+                    if let Some(matrix) = as_matrix(&operation.operands) {
+                        ctm = multiply(&matrix, &ctm);
+                    }
+                }
+                "Do" => {
+                    let Some(id) = operation
+                        .operands
+                        .first()
+                        .and_then(|x| x.as_name().ok())
+                        .and_then(|name| find_xobject(self.doc, resources, name))
+                    else {
+                        continue;
+                    };
+                    let Ok(stream) = self.doc.get_object(id).and_then(Object::as_stream) else {
+                        continue;
+                    };
+                    match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+                        Ok(b"Image") => placed.push((id, ctm)),
+                        Ok(b"Form")
+                            if !self.forms.contains(&id) && self.forms.len() < MAX_NESTING =>
+                        {
+                            let matrix = stream
+                                .dict
+                                .get(b"Matrix")
+                                .and_then(Object::as_array)
+                                .ok()
+                                .and_then(|x| as_matrix(x))
+                                .unwrap_or(IDENTITY);
+                            let Some(key) = self.form(id, stream, resources, owner) else {
+                                break;
+                            };
+                            let inner = &self.placed[&key];
+                            if !self.budget.spend(inner.len() as u64) {
+                                break;
+                            }
+                            let to_page = multiply(&matrix, &ctm);
+                            let room = MAX_PLACEMENTS - placed.len();
+                            placed.extend(
+                                inner
+                                    .iter()
+                                    .take(room)
+                                    .map(|(image, m)| (*image, multiply(m, &to_page))),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        placed
+    }
+
+    /// Places the images drawn by a form, unless they were already placed during this walk.
+    /// Returns the key they are kept under, or `None` if the walk ran out of budget.
+    fn form(
+        &mut self,
+        id: ObjectId,
+        stream: &Stream,
+        resources: &[&Dictionary],
+        owner: Option<ObjectId>,
+    ) -> Option<(ObjectId, Option<ObjectId>)> {
+        let own_resources = stream
+            .dict
+            .get(b"Resources")
+            .and_then(|x| self.doc.dereference(x))
+            .and_then(|x| x.1.as_dict());
+        let (key, inner_resources, inner_owner) = match own_resources {
+            Ok(dict) => ((id, Some(id)), vec![dict], Some(id)),
+            Err(_) => ((id, owner), resources.to_vec(), owner),
+        };
+        if !self.placed.contains_key(&key) {
+            let content = stream
+                .decompressed_content()
+                .unwrap_or_else(|_| stream.content.clone());
+            self.forms.push(id);
+            let placed = self.content(&content, &inner_resources, inner_owner);
+            self.forms.pop();
+            self.placed.insert(key, placed);
+        }
+        (!self.budget.is_spent()).then_some(key)
+    }
+}
@@ -1,226 +1,344 @@
-use std::{collections::HashSet, error::Error, vec};
-
-use lopdf::{Dictionary, Document, Object, ObjectId};
-
-use crate::{clean::Cleaner, models::page_type};
+use std::{collections::HashSet, error::Error};
+
+use lopdf::{Document, Object, ObjectId};
+
+use crate::{
+    clean::{CleanOptions, Cleaner},
+    limits::Budget,
+    models::{
+        analysis::{DocumentAnalysis, PageAnalysis},
+        docsity::{docsity_layout, DocsityLayout},
+        geometry::{content_image_placements, covers, set_page_boxes},
+        page_type::{self, PageType},
+        plan::{PageAction, PagePlan},
+        resources::{collect_page_images, PageImage},
+        signature::{AdSignature, AdSignatures},
+        studocu::strip_banners,
+    },
+};
 
 #[derive(Debug)]
 pub enum Method {
     /// The Wuolah method, which takes a vector of vectors of tuples containing unsigned integers and unsigned shorts,
     /// and a vector of unsigned integers as parameters.
     Wuolah(Vec<Vec<(u32, u16)>>, Vec<u32>),
-    /// The StuDocu method, which takes a vector of vectors of tuples containing unsigned integers and unsigned shorts
-    /// as a parameter.
-    StuDocu(Vec<Vec<(u32, u16)>>),
+    /// The StuDocu method, which removes the streams StuDocu added to each page and deletes its
+    /// cover. Pages without the StuDocu stamp are kept as they are.
+    StuDocu,
     /// The Naive method, which does not take any parameters.
     Naive,
+    /// The Docsity method, which deletes the Docsity cover and promo pages and crops the footer
+    /// banners away from the other pages. It takes the signatures of the footer banners, to find
+    /// the ones whose link was removed.
+    Docsity(Vec<AdSignature>),
+}
+
+impl Method {
+    /// The numerical code of the method: 0 for "Wuolah", 1 for "StuDocu", 2 for "Naive", and 3
+    /// for "Docsity".
+    pub fn code(&self) -> u8 {
+        match self {
+            Method::Wuolah(..) => 0,
+            Method::StuDocu => 1,
+            Method::Naive => 2,
+            Method::Docsity(..) => 3,
+        }
+    }
+
+    /// The name of the method.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Wuolah(..) => "Wuolah",
+            Method::StuDocu => "StuDocu",
+            Method::Naive => "Naive",
+            Method::Docsity(..) => "Docsity",
+        }
+    }
 }
 
 /// Implements the `Cleaner` trait for the `Method` enum.
-/// This method cleans the document based on the selected method.
-/// It modifies the contents and properties of the document's pages.
-/// Returns a tuple containing the pages to delete and a status code.
+/// Planning decides, for each page, what the selected method does with it. The plans are then
+/// applied to the document by `apply_plan`.
 impl Cleaner for Method {
-    fn clean(&mut self, doc: &mut Document) -> (Vec<u32>, u8) {
-        println!("Cleaning with method: {self:?}");
+    fn plan(
+        &self,
+        doc: &Document,
+        analysis: &DocumentAnalysis,
+        options: &CleanOptions,
+    ) -> Vec<PagePlan> {
+        let pages = &analysis.pages;
         match self {
             Method::Wuolah(content_list, to_delete) => {
+                let to_delete: HashSet<&u32> = to_delete.iter().collect();
                 let new_contents: Vec<Vec<(u32, u16)>> = content_list
                     .iter()
                     .enumerate()
                     .map(|(i, x)| {
-                        let pares = if x == content_list.last().unwrap() {
-                            find_iobj_pairs(x, &content_list[i - 1])
-                        } else {
-                            let check_if_00 = find_iobj_pairs(x, &content_list[i + 1]);
-                            if check_if_00 == (0, 0) {
-                                let check_again_if_00 = find_iobj_pairs(x, &content_list[i - 1]);
-                                if check_again_if_00 == (0, 0) {
-                                    (0, 0)
-                                } else {
-                                    check_again_if_00
-                                }
-                            } else {
-                                check_if_00
-                            }
+                        let previous = i.checked_sub(1).and_then(|i| content_list.get(i));
+                        let pares = match content_list
+                            .get(i + 1)
+                            .map(|next| find_iobj_pairs(x, next))
+                        {
+                            Some(check_if_00) if check_if_00 != (0, 0) => check_if_00,
+                            _ => previous.map_or((0, 0), |previous| find_iobj_pairs(x, previous)),
                         };
 
-                        x[(pares.0) - 2..=(pares.1) + 3].to_vec()
+                        // Pages whose shared streams cannot be found keep all their contents.
+                        match pares
+                            .0
+                            .checked_sub(2)
+                            .and_then(|start| x.get(start..=pares.1 + 3))
+                        {
+                            Some(kept) => kept.to_vec(),
+                            None => x.clone(),
+                        }
                     })
                     .collect();
 
-                let pages = doc.get_pages();
-
-                let vector: Vec<(&u32, &(u32, u16))> = pages
+                let mut new_contents = new_contents.into_iter();
+                pages
                     .iter()
-                    .filter(|x| doc.get_page_contents(*x.1).len() > 3)
-                    .collect();
-                for (i, page) in vector.iter().enumerate() {
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    let contents_objects: Vec<Object> = new_contents[i]
-                        .iter()
-                        .map(|x| Object::Reference(*x))
-                        .collect();
-
-                    mutable_page.set(*b"Contents", lopdf::Object::Array(contents_objects));
-
-                    mutable_page.set("Annots", Object::Array(vec![]));
-                    let mediabox = mutable_page.get(b"MediaBox").unwrap().as_array().unwrap();
-
-                    let height_offset = match mediabox[1].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[1].as_i64().unwrap() as f32,
-                    };
-                    let width_offset = match mediabox[0].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[0].as_i64().unwrap() as f32,
-                    };
-
-                    let height = match mediabox[3].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[3].as_i64().unwrap() as f32,
-                    };
-                    let width = match mediabox[2].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[2].as_i64().unwrap() as f32,
-                    };
-
-                    for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                        mutable_page.set(
-                            _box,
-                            Object::Array(vec![
-                                Object::Real(0.0),
-                                Object::Real(0.0),
-                                Object::Real(width - width_offset),
-                                Object::Real(height - height_offset),
-                            ]),
-                        );
-                    }
-                }
-
-                (to_delete.to_vec(), 0)
-            }
-            Method::StuDocu(content_list) => {
-                let new_contents: Vec<Vec<(u32, u16)>> =
-                    content_list.iter().skip(1).map(|x| vec![x[1]]).collect();
-                let pages = doc.get_pages();
-                let vector: Vec<(&u32, &(u32, u16))> = pages.iter().filter(|x| *x.0 != 1).collect();
-                for (i, page) in vector.iter().enumerate() {
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    let contents_objects: Vec<Object> = new_contents[i]
-                        .iter()
-                        .map(|x| Object::Reference(*x))
-                        .collect();
-
-                    mutable_page.set(*b"Contents", lopdf::Object::Array(contents_objects));
-
-                    mutable_page.set("Annots", Object::Array(vec![]));
-                }
-                (vec![1], 1)
+                    .map(|page| {
+                        // Ads can look like the original pages, so every page with more than
+                        // three streams takes its entry, whether it is deleted or not.
+                        let kept = (page.contents.len() > 3)
+                            .then(|| new_contents.next().unwrap_or_default());
+                        let action = if to_delete.contains(&page.number) {
+                            PageAction::Delete
+                        } else if let Some(contents) = kept {
+                            PageAction::ReplaceContents {
+                                contents,
+                                rect: page
+                                    .media_box
+                                    .map(|[x0, y0, x1, y1]| [0.0, 0.0, x1 - x0, y1 - y0]),
+                            }
+                        } else {
+                            PageAction::Keep
+                        };
+                        PagePlan {
+                            number: page.number,
+                            id: page.id,
+                            page_type: page.page_type,
+                            action,
+                            logos: vec![],
+                            banners: vec![],
+                            incomplete: false,
+                        }
+                    })
+                    .collect()
             }
+            Method::StuDocu => pages
+                .iter()
+                .map(|page| {
+                    let layout = page.studocu(doc, &analysis.budget);
+                    PagePlan {
+                        number: page.number,
+                        id: page.id,
+                        page_type: page.page_type,
+                        action: match layout {
+                            Some(layout) if layout.cover => PageAction::Delete,
+                            Some(layout) => PageAction::ReplaceContents {
+                                contents: layout.content.clone(),
+                                rect: None,
+                            },
+                            None => PageAction::Keep,
+                        },
+                        logos: vec![],
+                        banners: match layout {
+                            Some(layout) if !layout.cover => layout.banners.clone(),
+                            _ => vec![],
+                        },
+                        incomplete: layout.is_some_and(|layout| layout.incomplete),
+                    }
+                })
+                .collect(),
+            Method::Docsity(footers) => pages
+                .iter()
+                .map(|page| {
+                    let layout = docsity_layout(doc, page, footers, &analysis.budget);
+                    PagePlan {
+                        number: page.number,
+                        id: page.id,
+                        page_type: page.page_type,
+                        action: match &layout {
+                            Some(layout) if layout.promo => PageAction::Delete,
+                            Some(DocsityLayout {
+                                crop: Some(rect), ..
+                            }) => PageAction::Crop(*rect),
+                            _ => PageAction::Keep,
+                        },
+                        logos: match layout {
+                            Some(layout) if !layout.promo => layout.footers,
+                            _ => vec![],
+                        },
+                        banners: vec![],
+                        incomplete: false,
+                    }
+                })
+                .collect(),
 
             Method::Naive => {
-                println!("Using naive method");
-                let mut to_delete = Vec::new();
-                let pages = doc.get_pages();
-                for page in &pages {
-                    let page_type =
-                        page_type::PageType::get_page_type(doc, page.1).unwrap_or_default();
-                        println!("{page_type:?}");
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-
-                    let mediabox = mutable_page.get(b"MediaBox").unwrap().as_array().unwrap();
-                    let height_offset = match mediabox[1].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[1].as_i64().unwrap() as f32,
-                    };
-                    let width_offset = match mediabox[0].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[0].as_i64().unwrap() as f32,
-                    };
-
-                    let height = match mediabox[3].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[3].as_i64().unwrap() as f32,
-                    };
-                    let width = match mediabox[2].as_f32() {
-                        Ok(h) => h,
-                        _ => mediabox[2].as_i64().unwrap() as f32,
-                    };
-
-                    match page_type {
-                        page_type::PageType::FullPageAds => to_delete.push(*page.0),
-                        page_type::PageType::Idk => to_delete.push(*page.0),
-                        page_type::PageType::BannerAds => {
-                            //1.141
-                            let scale = 1.124;
-                            for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                                mutable_page.set(
-                                    _box,
-                                    Object::Array(vec![
-                                        Object::Real(
-                                            0.164 * (width - width_offset) + width_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.031 * (height - height_offset)
-                                                + height_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.978 * (width - width_offset) * scale
-                                                + width_offset * scale,
-                                        ),
-                                        Object::Real(
-                                            0.865 * (height - height_offset) * scale
-                                                + height_offset * scale,
-                                        ),
-                                    ]),
-                                );
+                pages
+                    .iter()
+                    .map(|page| {
+                        let page_type = page.page_type;
+                        let action = match (page_type, page.media_box.ok_or(())) {
+                            (page_type::PageType::FullPageAds, _) => PageAction::Delete,
+                            (page_type::PageType::Idk, _) => PageAction::Delete,
+                            (page_type::PageType::BannerAds, Ok([x0, y0, x1, y1])) => {
+                                //1.141
+                                let scale = 1.124;
+                                PageAction::Rescale {
+                                    scale,
+                                    rect: [
+                                        0.164 * (x1 - x0) + x0 * scale,
+                                        0.031 * (y1 - y0) + y0 * scale,
+                                        0.978 * (x1 - x0) * scale + x0 * scale,
+                                        0.865 * (y1 - y0) * scale + y0 * scale,
+                                    ],
+                                }
                             }
+                            (page_type::PageType::Watermark, Ok([x0, y0, x1, y1])) => {
+                                PageAction::Crop([
+                                    0.015 * (x1 - x0) + x0,
+                                    0.05 * (y1 - y0) + y0,
+                                    0.95 * (x1 - x0) + x0,
+                                    0.98 * (y1 - y0) + y0,
+                                ])
+                            }
+                            (_, Err(_)) => PageAction::Keep,
+                        };
 
-                            let mut contents = doc.get_page_content(*page.1).unwrap();
-                            let mut new_contents = Vec::new();
-                            let c_prepend = "q\n1.124 0 0 1.124 0 0 cm\n".as_bytes();
-                            let c_append = "Q".as_bytes();
+                        PagePlan {
+                            number: page.number,
+                            id: page.id,
+                            page_type,
+                            action,
+                            logos: logos_in(doc, &page.images, &options.signatures),
+                            banners: vec![],
+                            incomplete: false,
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
 
-                            new_contents.extend_from_slice(c_prepend);
-                            new_contents.append(&mut contents);
-                            new_contents.extend_from_slice(c_append);
+/// Whether a page of a Wuolah document is an ad inserted between the original pages.
+///
+/// The original pages are drawn between many streams shared with each other, while inserted
+/// pages usually have a few streams of their own, so pages with three streams or less are ads
+/// when they show any sign of one: a full page ad image, a link through the Wuolah tracker, or
+/// an image drawn over the whole page. Ads laid out like the original pages need stronger
+/// evidence, a tracking link over most of the page or a full page ad image drawn over all of it,
+/// as the original pages also carry tracking links in their banners and footers.
+pub fn is_inserted_ad(doc: &Document, page: &PageAnalysis, budget: &Budget) -> bool {
+    let page_box = page.media_box.unwrap_or([0.0, 0.0, 595.0, 842.0]);
+    let covers_page = || {
+        content_image_placements(doc, &page.id, page.content(doc), budget)
+            .unwrap_or_default()
+            .iter()
+            .any(|placement| covers(&placement.rect, &page_box, FULL_PAGE_COVERAGE))
+    };
+    let mut tracking_links = page.links.iter().filter(page_type::is_annots_wuolah);
 
-                            doc.change_page_content(*page.1, new_contents).unwrap()
-                        }
-                        page_type::PageType::Watermark => {
-                            for _box in ["MediaBox", "ArtBox", "TrimBox", "CropBox", "BleedBox"] {
-                                mutable_page.set(
-                                    _box,
-                                    Object::Array(vec![
-                                        Object::Real(0.015 * (width - width_offset) + width_offset),
-                                        Object::Real(
-                                            0.05 * (height - height_offset) + height_offset,
-                                        ),
-                                        Object::Real(0.95 * (width - width_offset) + width_offset),
-                                        Object::Real(
-                                            0.98 * (height - height_offset) + height_offset,
-                                        ),
-                                    ]),
-                                );
-                            }
-                        }
-                    }
-                }
+    if page.contents.len() < 4 {
+        page.page_type == PageType::FullPageAds || tracking_links.next().is_some() || covers_page()
+    } else {
+        tracking_links.any(|link| {
+            link.rect
+                .is_some_and(|rect| covers(&rect, &page_box, TRACKING_LINK_COVERAGE))
+        }) || (page.page_type == PageType::FullPageAds && covers_page())
+    }
+}
 
-                for page in &pages {
-                    // remove the logo
-                    let _ = remove_logo(doc, page.1);
+/// The share of the page an image must cover to be taken for a full page ad.
+const FULL_PAGE_COVERAGE: f32 = 0.9;
+
+/// The share of the page a tracking link must cover to be taken for the link of a full page ad.
+/// Banner links cover at most a fifth of the page.
+const TRACKING_LINK_COVERAGE: f32 = 0.5;
+
+/// Applies the plan of every page to the document, which was analysed into `analysis`.
+///
+/// Every page that is not deleted also has its annotations removed.
+/// Returns the numbers of the pages that should be deleted, or the number of the page that
+/// could not be modified along with the error.
+pub fn apply_plan(
+    doc: &mut Document,
+    analysis: &DocumentAnalysis,
+    plans: &[PagePlan],
+) -> Result<Vec<u32>, (u32, Box<dyn Error>)> {
+    let mut to_delete = Vec::new();
+    for plan in plans {
+        if plan.action == PageAction::Delete {
+            to_delete.push(plan.number);
+        }
+        apply_page_plan(doc, analysis, plan).map_err(|e| (plan.number, e))?;
+    }
+    Ok(to_delete)
+}
+
+fn apply_page_plan(
+    doc: &mut Document,
+    analysis: &DocumentAnalysis,
+    plan: &PagePlan,
+) -> Result<(), Box<dyn Error>> {
+    // remove the logos
+    for logo in &plan.logos {
+        let mutable_logo = &mut doc.get_object_mut(*logo)?.as_stream_mut()?.dict;
+        mutable_logo.set(*b"Height", 0);
+    }
+
+    // remove the text banners
+    for id in &plan.banners {
+        let stream = doc.get_object_mut(*id)?.as_stream_mut()?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        if let Some(stripped) = strip_banners(&content) {
+            stream.set_plain_content(stripped);
+        }
+    }
 
-                    // remove the annotations
-                    let mutable_page = doc.get_object_mut(*page.1).unwrap().as_dict_mut().unwrap();
-                    mutable_page.set("Annots", Object::Array(vec![]));
-                }
+    let mutable_page = doc.get_object_mut(plan.id)?.as_dict_mut()?;
+    match &plan.action {
+        PageAction::Keep => {}
+        PageAction::Delete => return Ok(()),
+        PageAction::Crop(rect) => set_page_boxes(mutable_page, *rect),
+        PageAction::Rescale { scale, rect } => {
+            set_page_boxes(mutable_page, *rect);
+
+            let mut contents = match analysis.page(plan.number) {
+                Some(page) => page.content(doc).to_vec(),
+                None => doc.get_page_content(plan.id)?,
+            };
+            let mut new_contents = Vec::new();
+            let c_prepend = format!("q\n{scale} 0 0 {scale} 0 0 cm\n");
+            let c_append = "Q".as_bytes();
+
+            new_contents.extend_from_slice(c_prepend.as_bytes());
+            new_contents.append(&mut contents);
+            new_contents.extend_from_slice(c_append);
+
+            doc.change_page_content(plan.id, new_contents)?
+        }
+        PageAction::ReplaceContents { contents, rect } => {
+            let contents_objects: Vec<Object> =
+                contents.iter().map(|x| Object::Reference(*x)).collect();
 
-                (to_delete, 2)
+            mutable_page.set(*b"Contents", lopdf::Object::Array(contents_objects));
+            if let Some(rect) = rect {
+                set_page_boxes(mutable_page, *rect);
             }
         }
     }
+
+    // remove the annotations
+    let mutable_page = doc.get_object_mut(plan.id)?.as_dict_mut()?;
+    mutable_page.set("Annots", Object::Array(vec![]));
+    Ok(())
 }
 
 pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) -> (usize, usize) {
@@ -230,7 +348,6 @@ pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) ->
     let c: Vec<&&(u32, u16)> = unique_first_page
         .intersection(&unique_second_page)
         .collect();
-    println!("{c:?}");
 
     //It seems like the indexes are always c.len() - 3 and c.len() - 2, except for the len == 2 case.
 
@@ -238,7 +355,7 @@ pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) ->
         .iter()
         .map(|&&element| first_page.iter().position(|&r| r == *element).unwrap())
         .collect();
-    
+
     indexes.sort();
 
     let len = indexes.len();
@@ -253,8 +370,39 @@ pub fn find_iobj_pairs(first_page: &[(u32, u16)], second_page: &[(u32, u16)]) ->
 }
 
 pub fn remove_logo(doc: &mut Document, page: &ObjectId) -> Result<(), Box<dyn Error>> {
-    let xobjs = get_xobjs(doc, page)?.clone();
-    let images = get_images(doc, &xobjs)?;
+    remove_logo_with(doc, page, AdSignatures::builtin())
+}
+
+/// Removes the logos from a page, using a custom set of ad signatures to recognise them.
+pub fn remove_logo_with(
+    doc: &mut Document,
+    page: &ObjectId,
+    signatures: &AdSignatures,
+) -> Result<(), Box<dyn Error>> {
+    for id in find_logos(doc, page, signatures)? {
+        let mutable_page = &mut doc.get_object_mut(id)?.as_stream_mut()?.dict;
+        mutable_page.set(*b"Height", 0);
+    }
+    Ok(())
+}
+
+/// Finds the logo images drawn by a page, using a set of ad signatures to recognise them.
+pub fn find_logos(
+    doc: &Document,
+    page: &ObjectId,
+    signatures: &AdSignatures,
+) -> Result<Vec<ObjectId>, Box<dyn Error>> {
+    Ok(logos_in(doc, &collect_page_images(doc, page)?, signatures))
+}
+
+/// Finds the logos among the images drawn by a page.
+pub fn logos_in(doc: &Document, images: &[PageImage], signatures: &AdSignatures) -> Vec<ObjectId> {
+    let is_logo = |image: &PageImage| {
+        signatures
+            .logos
+            .iter()
+            .any(|signature| signature.matches(doc, image))
+    };
 
     // let has_logo = !page_type::LOGO_DIMS
     //     .iter()
@@ -263,21 +411,20 @@ pub fn remove_logo(doc: &mut Document, page: &ObjectId) -> Result<(), Box<dyn Er
     //     .collect::<Vec<_>>()
     //     .is_empty();
 
-    let mut has_logo = images
-        .iter()
-        .any(|image| page_type::LOGO_DIMS.contains(image));
-    
+    let mut has_logo = images.iter().any(is_logo);
+
     //See if there are two images with the same dimensions
     let mut image_dims = HashSet::new();
     let mut repeated_logo_dims = (0, 0);
-    for image in images {
+    for image in images.iter().map(PageImage::dims) {
         if !image_dims.insert(image) {
-
             if image.1 == 0 || image.0 == 0 {
                 continue;
             }
 
-            if  (image.1 as f64 / image.0 as f64) > 5_f64 &&  (image.1 as f64) / (image.0 as f64) < 6_f64 {
+            if (image.1 as f64 / image.0 as f64) > 5_f64
+                && (image.1 as f64) / (image.0 as f64) < 6_f64
+            {
                 repeated_logo_dims = image;
                 has_logo = true;
                 break;
@@ -285,81 +432,12 @@ pub fn remove_logo(doc: &mut Document, page: &ObjectId) -> Result<(), Box<dyn Er
         }
     }
 
-    println!("{repeated_logo_dims:?}");
-
-
     if !has_logo {
-        return Ok(());
-    }
-    for obj in &xobjs {
-        let objectdict = get_objdict(doc, obj)?;
-
-        let subtype = objectdict.get(b"Subtype")?.as_name()?;
-
-        let sub_s = String::from_utf8_lossy(subtype);
-        if sub_s.starts_with("Image")
-            && (page_type::LOGO_DIMS.contains(&(
-                objectdict.get(b"Height")?.as_i64()?,
-                objectdict.get(b"Width")?.as_i64()?,
-            )) || repeated_logo_dims == (
-                objectdict.get(b"Height")?.as_i64()?,
-                objectdict.get(b"Width")?.as_i64()?,
-            )
-        )
-        {
-            let mutable_page = &mut doc
-                .get_object_mut(obj.1.as_reference()?)?
-                .as_stream_mut()?
-                .dict;
-            mutable_page.set(*b"Height", 0);
-        }
-    }
-    Ok(())
-}
-
-fn get_objdict<'a>(
-    doc: &'a Document,
-    obj: (&Vec<u8>, &Object),
-) -> Result<&'a Dictionary, Box<dyn Error>> {
-    let objdict = &doc
-        .get_object(obj.1.as_reference().unwrap())?
-        .as_stream()?
-        .dict;
-
-    Ok(objdict)
-}
-
-pub fn get_xobjs<'a>(doc: &'a Document, page: &ObjectId) -> Result<&'a Dictionary, Box<dyn Error>> {
-    let resource = doc.get_page_resources(*page)?;
-    let resource_dict: &Dictionary = if resource.1.is_empty() {
-        resource.0.unwrap()
-    } else {
-        doc.get_object(resource.1[0])?.as_dict()?
-    };
-    let xobjs = match resource_dict.get(b"XObject")? {
-        Object::Dictionary(x) => x,
-        Object::Reference(x) => doc.get_object(*x)?.as_dict()?,
-        _ => return Err("Error".into()),
-    };
-    Ok(xobjs)
-}
-
-pub fn get_images(doc: &Document, xobjs: &Dictionary) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
-    let mut images = Vec::new();
-
-    for obj in xobjs {
-        let objectdict = get_objdict(doc, obj)?;
-
-        let subtype = objectdict.get(b"Subtype").unwrap().as_name().unwrap();
-        let sub_s = String::from_utf8_lossy(subtype);
-
-        if sub_s.starts_with("Image") {
-            images.push((
-                objectdict.get(b"Height").unwrap().as_i64().unwrap(),
-                objectdict.get(b"Width").unwrap().as_i64().unwrap(),
-            ));
-        }
+        return vec![];
     }
-
-    Ok(images)
+    images
+        .iter()
+        .filter(|image| is_logo(image) || repeated_logo_dims == image.dims())
+        .map(|image| image.id)
+        .collect()
 }
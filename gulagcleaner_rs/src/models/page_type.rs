@@ -2,9 +2,14 @@ use std::{collections::HashSet, error::Error};
  
 use lopdf::{Document, ObjectId};
  
-use super::method::{get_images, get_xobjs};
+use super::{
+    analysis::{page_links, Link},
+    resources::{collect_page_images, PageImage},
+    signature::{AdSignature, AdSignatures},
+};
  
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 /// Represents the different methods used in the Gulag Cleaner application.
 pub enum PageType {
     BannerAds,
@@ -16,7 +21,7 @@ pub enum PageType {
  
 pub const LOGO_DIMS: [(i64, i64); 9] = [(71, 390), (37, 203), (73, 390), (23, 130), (24, 130), (19, 109), (20, 109), (72, 391), (24, 129)];
  
-const HORIZONTAL_BANNER_DIMS: [(i64, i64); 13] = [
+pub const HORIZONTAL_BANNER_DIMS: [(i64, i64); 13] = [
     (247, 1414),
     (213, 1219),
     (215, 1219),
@@ -31,7 +36,7 @@ const HORIZONTAL_BANNER_DIMS: [(i64, i64); 13] = [
     (218,1246),
     (217,1094)
 ];
-const VERTICAL_BANNER_DIMS: [(i64, i64); 14] = [
+pub const VERTICAL_BANNER_DIMS: [(i64, i64); 14] = [
     (1753, 170),
     (1518, 248),
     (1520, 147),
@@ -47,7 +52,7 @@ const VERTICAL_BANNER_DIMS: [(i64, i64); 14] = [
     (1538, 147),
     (1769,148)
 ];
-const FULL_PAGE_DIMS: [(i64, i64); 10] = [
+pub const FULL_PAGE_DIMS: [(i64, i64); 10] = [
     (842, 595),
     (1754, 1240),
     (2526, 1785),
@@ -60,12 +65,19 @@ const FULL_PAGE_DIMS: [(i64, i64); 10] = [
     (1759, 1241),
 ];
  
-/// Check if dimension matches any dimension from a list within a tolerance of 10 units.
-fn matches_with_tolerance(dims: &[(i64, i64)], images: &HashSet<(i64, i64)>) -> bool {
-    dims.iter().any(|&(w, h)| {
-        images.iter().any(|&(iw, ih)| {
-            (iw >= w - 10 && iw <= w + 10) && (ih >= h - 10 && ih <= h + 10)
-        })
+/// Check if any image matches one of the signatures.
+///
+/// Hashed signatures are only checked against real images, while size-only signatures are also
+/// checked against the synthetic `dims` built by combining images.
+fn matches_signatures(
+    doc: &Document,
+    signatures: &[AdSignature],
+    images: &[PageImage],
+    dims: &HashSet<(i64, i64)>,
+) -> bool {
+    signatures.iter().any(|signature| {
+        images.iter().any(|image| signature.matches(doc, image))
+            || (!signature.is_hashed() && dims.iter().any(|&d| signature.matches_dims(d)))
     })
 }
 
@@ -90,57 +102,56 @@ fn generate_combined_dims(images: &HashSet<(i64, i64)>) -> HashSet<(i64, i64)> {
 impl PageType {
     /// Get the type of a page based on its content.
     pub fn get_page_type(doc: &Document, page: &ObjectId) -> Result<PageType, Box<dyn Error>> {
-        let xobjs = get_xobjs(doc, page)?;
-        let images = get_images(doc, xobjs)?;
-        let image_set: HashSet<(i64, i64)> = images.into_iter().collect();
-        fn scaled_image_set(images: &HashSet<(i64, i64)>) -> HashSet<(i64, i64)> {
-            images.iter().map(|&(w, h)| (w, h * 2)).collect()
-        }
-        let scaled_images = scaled_image_set(&image_set);
-        let combined_dims = generate_combined_dims(&image_set);
+        Self::get_page_type_with(doc, page, AdSignatures::builtin())
+    }
 
-        // we compare against combined, scaled and unscaled sets to ensure backwards compatiblity
-        let has_horizontal_banner = matches_with_tolerance(&HORIZONTAL_BANNER_DIMS, &combined_dims) ||
-                                    matches_with_tolerance(&HORIZONTAL_BANNER_DIMS, &image_set);
-                                    matches_with_tolerance(&HORIZONTAL_BANNER_DIMS, &scaled_images);
+    /// Get the type of a page based on its content, using a custom set of ad signatures.
+    pub fn get_page_type_with(
+        doc: &Document,
+        page: &ObjectId,
+        signatures: &AdSignatures,
+    ) -> Result<PageType, Box<dyn Error>> {
+        let images = collect_page_images(doc, page)?;
+        let links = page_links(doc, page)?;
+        Ok(Self::classify(doc, &images, &links, signatures))
+    }
+
+    /// Get the type of a page from the images it draws and its links.
+    pub fn classify(
+        doc: &Document,
+        images: &[PageImage],
+        links: &[Link],
+        signatures: &AdSignatures,
+    ) -> PageType {
+        let image_set: HashSet<(i64, i64)> = images.iter().map(PageImage::dims).collect();
+        let combined_dims = generate_combined_dims(&image_set);
 
-        let has_vertical_banner = matches_with_tolerance(&VERTICAL_BANNER_DIMS, &combined_dims) ||
-                                  matches_with_tolerance(&VERTICAL_BANNER_DIMS, &image_set);
-                                  matches_with_tolerance(&VERTICAL_BANNER_DIMS, &scaled_images);
+        // we compare against combined and unscaled sets to ensure backwards compatiblity
+        let has_horizontal_banner =
+            matches_signatures(doc, &signatures.horizontal_banners, images, &combined_dims);
+        let has_vertical_banner =
+            matches_signatures(doc, &signatures.vertical_banners, images, &combined_dims);
 
-        let has_full_page = matches_with_tolerance(&FULL_PAGE_DIMS, &image_set);
+        let has_full_page = matches_signatures(doc, &signatures.full_page, images, &HashSet::new());
  
         if has_horizontal_banner && has_vertical_banner {
-            Ok(PageType::BannerAds)
+            PageType::BannerAds
         } else if has_full_page {
-            Ok(PageType::FullPageAds)
+            PageType::FullPageAds
         } else {
-            let annots = doc.get_page_annotations(*page)?;
- 
-            let wuolah_annot = annots
+            let wuolah_annot = links
                 .iter()
-                .filter(|x| is_annots_wuolah(x, doc))
-                .filter(|x| {
-                    x.get(b"Rect").unwrap().as_array().unwrap()[0] == lopdf::Object::Integer(0)
-                        || x.get(b"Rect").unwrap().as_array().unwrap()[0] == lopdf::Object::Real(0.0)
-                });
+                .filter(is_annots_wuolah)
+                .filter(|x| x.rect.is_some_and(|rect| rect[0] == 0.0));
             //let mut bannercounter = 0;
             let mut hasfooter = false;
             for annot in wuolah_annot {
-                if let Ok(action) = annot.get(b"A") {
-                    if let Ok(action_dict) = doc.dereference(action) {
-                        if let Ok(uri) = action_dict.1.as_dict().unwrap().get(b"URI") {
-                            if let Ok(url) = doc.dereference(uri) {
-                                let url_str = url.1.as_string().unwrap();
-                                /*if url_str.contains("adU=2") {
-                                    bannercounter += 1;
-                                }*/
-                                if url_str.contains("adU=2") {
-                                    hasfooter = true;
-                                }
-                            }
-                        }
-                    }
+                let url_str = annot.uri.as_deref().unwrap_or_default();
+                /*if url_str.contains("adU=2") {
+                    bannercounter += 1;
+                }*/
+                if url_str.contains("adU=2") {
+                    hasfooter = true;
                 }
             }
             /*if bannercounter == 1 {
@@ -150,26 +161,16 @@ impl PageType {
                 return Ok(PageType::BannerAds);
             }*/
             if hasfooter {
-                return Ok(PageType::Watermark);
+                return PageType::Watermark;
             }
-            Ok(PageType::Idk)
+            PageType::Idk
         }
     }
 }
  
-fn is_annots_wuolah(annot: &&&lopdf::Dictionary, doc: &lopdf::Document) -> bool {
-    match annot.get(b"A") {
-        Ok(x) => match doc.dereference(x).unwrap().1.as_dict().unwrap().get(b"URI") {
-            Ok(y) => {
-                let url = doc.dereference(y).unwrap().1.as_string().unwrap();
-                if url.contains("track.wlh.es") {
-                    !(url.contains("apuntes"))
-                } else {
-                    false
-                }
-            }
-            Err(_) => false,
-        },
-        Err(_) => false,
+pub(crate) fn is_annots_wuolah(link: &&Link) -> bool {
+    match &link.uri {
+        Some(url) => url.contains("track.wlh.es") && !url.contains("apuntes"),
+        None => false,
     }
-}
\ No newline at end of file
+}
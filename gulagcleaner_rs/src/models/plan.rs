@@ -0,0 +1,60 @@
+use lopdf::ObjectId;
+
+use super::{geometry::Rect, page_type::PageType};
+
+/// What the cleaner does with a single page.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PageAction {
+    /// The page is kept as it is.
+    Keep,
+    /// The page is an ad and is deleted.
+    Delete,
+    /// The page boxes are set to the rectangle, cropping away the ads around it.
+    Crop(Rect),
+    /// The page contents are scaled up by `scale` and the page boxes set to `rect`.
+    Rescale { scale: f32, rect: Rect },
+    /// The page contents are replaced by `contents`, which drops the ads drawn by the other
+    /// streams. The page boxes are set to `rect` if there is one.
+    ReplaceContents {
+        contents: Vec<ObjectId>,
+        rect: Option<Rect>,
+    },
+}
+
+impl PageAction {
+    /// A short, human readable description of the action.
+    pub fn describe(&self) -> String {
+        match self {
+            PageAction::Keep => "keep".to_string(),
+            PageAction::Delete => "delete".to_string(),
+            PageAction::Crop(_) => "crop".to_string(),
+            PageAction::Rescale { scale, .. } => format!("rescale x{scale}"),
+            PageAction::ReplaceContents { contents, .. } => {
+                format!("replace contents ({} streams kept)", contents.len())
+            }
+        }
+    }
+}
+
+/// The decision taken by a method for a page of the original document.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PagePlan {
+    /// The 1-based page number in the original document.
+    pub number: u32,
+    /// The object id of the page dictionary.
+    pub id: ObjectId,
+    /// How the page was classified.
+    pub page_type: PageType,
+    /// What is done with the page.
+    pub action: PageAction,
+    /// The logo images that are hidden from the page.
+    pub logos: Vec<ObjectId>,
+    /// The content streams that are kept, with the text banners drawn by them removed.
+    pub banners: Vec<ObjectId>,
+    /// Whether the original content of the page is missing from the document, so that only a
+    /// preview of it is left after cleaning.
+    pub incomplete: bool,
+}
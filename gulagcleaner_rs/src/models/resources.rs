@@ -0,0 +1,136 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::limits::MAX_NESTING;
+
+/// An image XObject reachable from a page, together with the Form XObjects it is nested in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImage {
+    /// The object id of the image stream.
+    pub id: ObjectId,
+    /// The resource name the image is registered under in its innermost resource dictionary.
+    pub name: Vec<u8>,
+    /// The height of the image in samples.
+    pub height: i64,
+    /// The width of the image in samples.
+    pub width: i64,
+    /// The Form XObjects wrapping the image, outermost first. Empty if the page draws it directly.
+    pub forms: Vec<ObjectId>,
+}
+
+impl PageImage {
+    /// The `(height, width)` pair used by the dimension tables in `page_type`.
+    pub fn dims(&self) -> (i64, i64) {
+        (self.height, self.width)
+    }
+}
+
+/// Returns the resource dictionary that applies to a page, inline or referenced.
+///
+/// `/Resources` is inherited as a whole rather than merged, so this is the page's own
+/// dictionary, or else the one of its nearest ancestor in the page tree that has one.
+pub fn page_resources<'a>(
+    doc: &'a Document,
+    page: &ObjectId,
+) -> Result<Option<&'a Dictionary>, Box<dyn Error>> {
+    let mut seen = HashSet::new();
+    let mut node_id = *page;
+
+    while seen.insert(node_id) {
+        let node = doc.get_dictionary(node_id)?;
+        if let Ok(resources) = node.get(b"Resources") {
+            return Ok(doc.dereference(resources).and_then(|x| x.1.as_dict()).ok());
+        }
+        match node.get(b"Parent").and_then(Object::as_reference) {
+            Ok(parent) => node_id = parent,
+            Err(_) => break,
+        }
+    }
+
+    Ok(None)
+}
+
+/// Collects every image a page can draw, descending into Form XObjects.
+///
+/// XObjects are looked up in the resources that apply to the page, its own or else the ones it
+/// inherits from the page tree. Every form is only walked once for the whole page, so neither
+/// reference cycles nor forms shared by many parents can make the walk blow up, and forms
+/// nested deeper than `MAX_NESTING` are not walked. The images of a shared form are listed
+/// with the first chain of forms it was reached through.
+pub fn collect_page_images(
+    doc: &Document,
+    page: &ObjectId,
+) -> Result<Vec<PageImage>, Box<dyn Error>> {
+    let mut images = Vec::new();
+    let mut expanded = BTreeSet::new();
+
+    let resources = page_resources(doc, page)?;
+    if let Some(xobjs) = resources.and_then(|x| xobject_dict(doc, x)) {
+        for (name, obj) in xobjs {
+            collect_xobject(doc, name, obj, &mut Vec::new(), &mut expanded, &mut images);
+        }
+    }
+
+    Ok(images)
+}
+
+/// Returns the `/XObject` subdictionary of a resource dictionary, if it has one.
+fn xobject_dict<'a>(doc: &'a Document, resources: &'a Dictionary) -> Option<&'a Dictionary> {
+    let xobjs = resources.get(b"XObject").ok()?;
+    doc.dereference(xobjs).ok()?.1.as_dict().ok()
+}
+
+fn collect_xobject(
+    doc: &Document,
+    name: &[u8],
+    obj: &Object,
+    forms: &mut Vec<ObjectId>,
+    expanded: &mut BTreeSet<ObjectId>,
+    images: &mut Vec<PageImage>,
+) {
+    let Ok(id) = obj.as_reference() else {
+        return;
+    };
+    let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+        return;
+    };
+    let dict = &stream.dict;
+    let Ok(subtype) = dict.get(b"Subtype").and_then(Object::as_name) else {
+        return;
+    };
+
+    if subtype.starts_with(b"Image") {
+        let dim = |key: &[u8]| {
+            dict.get(key)
+                .and_then(|x| doc.dereference(x))
+                .and_then(|x| x.1.as_i64())
+                .unwrap_or(0)
+        };
+        images.push(PageImage {
+            id,
+            name: name.to_vec(),
+            height: dim(b"Height"),
+            width: dim(b"Width"),
+            forms: forms.clone(),
+        });
+    } else if subtype == b"Form" && forms.len() < MAX_NESTING && expanded.insert(id) {
+        let Some(xobjs) = dict
+            .get(b"Resources")
+            .and_then(|x| doc.dereference(x))
+            .ok()
+            .and_then(|x| x.1.as_dict().ok())
+            .and_then(|resources| xobject_dict(doc, resources))
+        else {
+            return;
+        };
+        forms.push(id);
+        for (inner_name, inner_obj) in xobjs {
+            collect_xobject(doc, inner_name, inner_obj, forms, expanded, images);
+        }
+        forms.pop();
+    }
+}
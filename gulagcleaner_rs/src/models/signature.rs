@@ -0,0 +1,248 @@
+use std::sync::OnceLock;
+
+use lopdf::{Document, Object, Stream};
+
+use super::{
+    page_type::{FULL_PAGE_DIMS, HORIZONTAL_BANNER_DIMS, LOGO_DIMS, VERTICAL_BANNER_DIMS},
+    resources::PageImage,
+};
+
+/// Maximum number of differing bits for two perceptual hashes to be considered the same image.
+pub const PERCEPTUAL_HASH_MAX_DISTANCE: u32 = 6;
+
+/// The most memory a JPEG may decode to for its perceptual hash. The largest ads are A4 pages
+/// at 300 DPI, which take about 26 MiB.
+const MAX_DECODED_IMAGE_BYTES: usize = 128 * 1024 * 1024;
+
+/// A fingerprint of a known ad image.
+///
+/// A signature always carries the `(height, width)` of the image. When it also carries a
+/// content or perceptual hash, an image of the right size only matches if one of the hashes
+/// does too, so that document images which happen to share the size of an ad are left alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdSignature {
+    /// The `(height, width)` of the ad image.
+    pub dims: (i64, i64),
+    /// How many units each dimension may differ from `dims`.
+    pub tolerance: i64,
+    /// The `content_hash` of the decoded image stream.
+    pub content_hash: Option<u64>,
+    /// The `perceptual_hash` of the image pixels.
+    pub perceptual_hash: Option<u64>,
+}
+
+impl AdSignature {
+    /// Creates a signature matching any image of the given dimensions, within `tolerance`.
+    pub fn from_dims(dims: (i64, i64), tolerance: i64) -> AdSignature {
+        AdSignature {
+            dims,
+            tolerance,
+            content_hash: None,
+            perceptual_hash: None,
+        }
+    }
+
+    /// Creates an exact signature from an image in a document, carrying both of its hashes.
+    pub fn from_image(doc: &Document, image: &PageImage) -> AdSignature {
+        AdSignature {
+            dims: image.dims(),
+            tolerance: 0,
+            content_hash: content_hash(doc, image),
+            perceptual_hash: perceptual_hash(doc, image),
+        }
+    }
+
+    pub fn with_content_hash(mut self, hash: u64) -> AdSignature {
+        self.content_hash = Some(hash);
+        self
+    }
+
+    pub fn with_perceptual_hash(mut self, hash: u64) -> AdSignature {
+        self.perceptual_hash = Some(hash);
+        self
+    }
+
+    /// Whether the signature identifies images by content rather than by size alone.
+    pub fn is_hashed(&self) -> bool {
+        self.content_hash.is_some() || self.perceptual_hash.is_some()
+    }
+
+    /// Checks the dimensions alone, ignoring any hashes.
+    pub fn matches_dims(&self, (h, w): (i64, i64)) -> bool {
+        (h - self.dims.0).abs() <= self.tolerance && (w - self.dims.1).abs() <= self.tolerance
+    }
+
+    /// Checks whether an image of a document is the ad described by this signature.
+    pub fn matches(&self, doc: &Document, image: &PageImage) -> bool {
+        if !self.matches_dims(image.dims()) {
+            return false;
+        }
+        if !self.is_hashed() {
+            return true;
+        }
+        if self.content_hash.is_some() && self.content_hash == content_hash(doc, image) {
+            return true;
+        }
+        match (self.perceptual_hash, perceptual_hash(doc, image)) {
+            (Some(expected), Some(actual)) => {
+                (expected ^ actual).count_ones() <= PERCEPTUAL_HASH_MAX_DISTANCE
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The ad signatures used to classify pages and find logos.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AdSignatures {
+    pub horizontal_banners: Vec<AdSignature>,
+    pub vertical_banners: Vec<AdSignature>,
+    pub full_page: Vec<AdSignature>,
+    pub logos: Vec<AdSignature>,
+}
+
+impl AdSignatures {
+    /// The signatures built from the dimension tables in `page_type`.
+    pub fn builtin() -> &'static AdSignatures {
+        static BUILTIN: OnceLock<AdSignatures> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let from_dims = |dims: &[(i64, i64)], tolerance| {
+                dims.iter()
+                    .map(|&dims| AdSignature::from_dims(dims, tolerance))
+                    .collect()
+            };
+            AdSignatures {
+                horizontal_banners: from_dims(&HORIZONTAL_BANNER_DIMS, 10),
+                vertical_banners: from_dims(&VERTICAL_BANNER_DIMS, 10),
+                full_page: from_dims(&FULL_PAGE_DIMS, 10),
+                logos: from_dims(&LOGO_DIMS, 0),
+            }
+        })
+    }
+}
+
+/// Returns the image stream data with every filter we know how to undo removed.
+///
+/// lopdf refuses to decompress streams with an image subtype, so this decodes a copy of the
+/// stream without it. Filters lopdf cannot undo (such as `DCTDecode`) are left in place.
+fn decoded_image_data(stream: &Stream) -> Vec<u8> {
+    let mut dict = stream.dict.clone();
+    dict.remove(b"Subtype");
+    let plain = Stream {
+        dict,
+        content: stream.content.clone(),
+        allows_compression: stream.allows_compression,
+        start_position: None,
+    };
+    plain
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone())
+}
+
+fn image_stream<'a>(doc: &'a Document, image: &PageImage) -> Option<&'a Stream> {
+    doc.get_object(image.id).and_then(Object::as_stream).ok()
+}
+
+/// A stable 64-bit FNV-1a hash of the decoded image stream.
+///
+/// Two images hash the same if and only if (barring collisions) their data is byte-for-byte
+/// identical after decompression, regardless of how each file compressed it.
+pub fn content_hash(doc: &Document, image: &PageImage) -> Option<u64> {
+    let data = decoded_image_data(image_stream(doc, image)?);
+    Some(data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    }))
+}
+
+/// A 64-bit difference hash of the image pixels.
+///
+/// The image is reduced to a 9x8 grayscale thumbnail and each bit records whether a pixel is
+/// brighter than its right neighbour, so re-encoded or slightly rescaled copies of the same
+/// creative land within a few bits of each other. Returns `None` for images whose pixels we
+/// cannot decode (anything other than 8-bit raw or JPEG data).
+pub fn perceptual_hash(doc: &Document, image: &PageImage) -> Option<u64> {
+    let stream = image_stream(doc, image)?;
+    let (width, height, gray) = grayscale_pixels(stream, image)?;
+
+    let mut thumbnail = [[0u64; 9]; 8];
+    let mut counts = [[0u64; 9]; 8];
+    for y in 0..height {
+        for x in 0..width {
+            let (ty, tx) = (y * 8 / height, x * 9 / width);
+            thumbnail[ty][tx] += gray[y * width + x] as u64;
+            counts[ty][tx] += 1;
+        }
+    }
+
+    let mut hash = 0u64;
+    for (row, row_counts) in thumbnail.iter().zip(counts.iter()) {
+        for x in 0..8 {
+            let left = row[x] / row_counts[x].max(1);
+            let right = row[x + 1] / row_counts[x + 1].max(1);
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+    Some(hash)
+}
+
+/// Decodes an image into one 8-bit luminance value per pixel.
+fn grayscale_pixels(stream: &Stream, image: &PageImage) -> Option<(usize, usize, Vec<u8>)> {
+    let is_jpeg = stream
+        .filters()
+        .ok()
+        .and_then(|filters| filters.last().cloned())
+        .is_some_and(|filter| filter == "DCTDecode");
+
+    let (width, height, components, pixels) = if is_jpeg {
+        let mut decoder = jpeg_decoder::Decoder::new(stream.content.as_slice());
+        decoder.set_max_decoding_buffer_size(MAX_DECODED_IMAGE_BYTES);
+        let pixels = decoder.decode().ok()?;
+        let info = decoder.info()?;
+        let components = match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 => 1,
+            jpeg_decoder::PixelFormat::RGB24 => 3,
+            jpeg_decoder::PixelFormat::CMYK32 => 4,
+            jpeg_decoder::PixelFormat::L16 => return None,
+        };
+        (
+            info.width as usize,
+            info.height as usize,
+            components,
+            pixels,
+        )
+    } else {
+        let bits = stream
+            .dict
+            .get(b"BitsPerComponent")
+            .and_then(Object::as_i64)
+            .unwrap_or(8);
+        let (width, height) = (image.width.max(0) as usize, image.height.max(0) as usize);
+        if bits != 8 || width == 0 || height == 0 {
+            return None;
+        }
+        let pixels = decoded_image_data(stream);
+        let components = pixels.len() / width.checked_mul(height)?;
+        (width, height, components, pixels)
+    };
+
+    if width == 0 || height == 0 || !matches!(components, 1 | 3 | 4) {
+        return None;
+    }
+    let gray = pixels
+        .chunks_exact(components)
+        .take(width * height)
+        .map(|px| match px {
+            [l] => *l,
+            [r, g, b] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8,
+            [c, m, y, k] => {
+                let ink = (*c as u32 * 299 + *m as u32 * 587 + *y as u32 * 114) / 1000;
+                (255 - (ink + *k as u32).min(255)) as u8
+            }
+            _ => 0,
+        })
+        .collect::<Vec<u8>>();
+    if gray.len() < width * height {
+        return None;
+    }
+    Some((width, height, gray))
+}
@@ -0,0 +1,409 @@
+use std::ops::Range;
+
+use lopdf::{
+    content::{Content, Operation},
+    Document, Object, ObjectId,
+};
+
+use super::geometry::{as_number, content_image_placements, covers, media_box, Rect};
+use crate::limits::Budget;
+
+/// The identifier StuDocu prints at the top of every page it serves, looked for in lowercase.
+const IDENTIFIER: &[u8] = b"lomoarcpsd";
+
+/// Text StuDocu writes on every page it serves, looked for in lowercase.
+///
+/// `lOMoARcPSD` is printed in a tiny font at the top of the page, and the "Downloaded by" line
+/// and the distribution notices, translated to the language of the download, at the bottom.
+pub const BANNER_MARKERS: [&[u8]; 7] = [
+    IDENTIFIER,
+    b"descargado por",
+    b"downloaded by",
+    b"distribuci\xf3n prohibida",
+    b"distribution prohibited",
+    b"studocu no est\xe1 patrocinado",
+    b"studocu is not sponsored",
+];
+
+/// The largest font size StuDocu prints its banners in. The identifier is printed at 1 point
+/// and the download details at 8.
+const MAX_BANNER_FONT_SIZE: f32 = 8.0;
+
+/// The most text showing operators in a banner, which StuDocu prints as a single line.
+const MAX_BANNER_TEXTS: usize = 2;
+
+/// Text StuDocu draws over the pages that are only previewed to users without premium, looked
+/// for in lowercase in the text the overlay shows.
+pub const OVERLAY_MARKERS: [&[u8]; 3] = [b"premium", b"desbloquea", b"unlock"];
+
+/// Overlays only draw a few lines of text, so streams that show more text than this are never
+/// taken for one, whatever they say.
+const MAX_OVERLAY_TEXT: usize = 16;
+
+/// The share of the page an image must cover to be taken for a blurred preview of it, or a fill
+/// for the veil of an overlay.
+const PREVIEW_COVERAGE: f32 = 0.9;
+
+/// How the content streams of a page served by StuDocu are laid out.
+///
+/// StuDocu wraps the original streams of each page between a stream that only saves the
+/// graphics state, shared by every page, and a stamp stream that restores it and draws the
+/// download details on top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuDocuLayout {
+    /// The streams that are kept, in the order they are drawn.
+    pub content: Vec<ObjectId>,
+    /// The streams StuDocu added to the page.
+    pub added: Vec<ObjectId>,
+    /// The kept streams that also draw StuDocu banners, which are removed from them.
+    pub banners: Vec<ObjectId>,
+    /// The streams that draw a premium overlay or a blurred preview over the page.
+    pub overlays: Vec<ObjectId>,
+    /// Whether the page is the StuDocu cover, which has nothing of the original document.
+    pub cover: bool,
+    /// Whether the original content of the page is missing from the download. The overlays of
+    /// these pages are kept, as the preview is all there is left of them.
+    pub incomplete: bool,
+}
+
+/// A content stream of a page, with what it draws.
+struct PageStream {
+    id: ObjectId,
+    content: Vec<u8>,
+    /// The number of text showing operators.
+    text: usize,
+    /// The text shown by those operators, in lowercase.
+    shown: Vec<u8>,
+    /// The rectangles it fills, as given to `re`.
+    fills: Vec<Rect>,
+    /// The number of XObjects drawn.
+    xobjects: usize,
+    /// Whether it paints paths, shadings or inline images.
+    paints: bool,
+    /// The number of text objects that draw a StuDocu banner.
+    banners: usize,
+    /// Whether one of those banners is the StuDocu identifier.
+    identified: bool,
+    /// Whether it draws anything besides the banners.
+    draws_more: bool,
+}
+
+impl PageStream {
+    fn new(doc: &Document, id: ObjectId) -> Option<PageStream> {
+        let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        let mut page_stream = PageStream {
+            id,
+            content,
+            text: 0,
+            shown: Vec::new(),
+            fills: Vec::new(),
+            xobjects: 0,
+            paints: false,
+            banners: 0,
+            identified: false,
+            draws_more: false,
+        };
+        let operations = Content::decode(&page_stream.content)
+            .map(|x| x.operations)
+            .unwrap_or_default();
+        let banners = banner_blocks(&operations);
+        page_stream.banners = banners.len();
+        page_stream.identified = banners.iter().any(|banner| banner.identifier);
+        // The rectangles of the path under construction.
+        let mut path: Vec<Rect> = Vec::new();
+        for (i, operation) in operations.iter().enumerate() {
+            let draws = match operation.operator.as_str() {
+                "Tj" | "TJ" | "'" | "\"" => {
+                    page_stream.text += 1;
+                    page_stream
+                        .shown
+                        .extend(shown_text(operation).to_ascii_lowercase());
+                    true
+                }
+                "Do" => {
+                    page_stream.xobjects += 1;
+                    true
+                }
+                "re" => {
+                    let numbers: Vec<f32> = operation
+                        .operands
+                        .iter()
+                        .filter_map(|x| as_number(x).ok())
+                        .collect();
+                    if let [x, y, width, height] = numbers[..] {
+                        path.push([
+                            x.min(x + width),
+                            y.min(y + height),
+                            x.max(x + width),
+                            y.max(y + height),
+                        ]);
+                    }
+                    false
+                }
+                "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => {
+                    page_stream.fills.append(&mut path);
+                    page_stream.paints = true;
+                    true
+                }
+                "S" | "s" | "sh" | "BI" => {
+                    path.clear();
+                    page_stream.paints = true;
+                    true
+                }
+                "n" => {
+                    path.clear();
+                    false
+                }
+                _ => false,
+            };
+            if draws && !banners.iter().any(|banner| banner.range.contains(&i)) {
+                page_stream.draws_more = true;
+            }
+        }
+        Some(page_stream)
+    }
+
+    /// Whether the stream is the stamp StuDocu appends to the page, which restores the graphics
+    /// state saved before the original streams, or only draws banners.
+    fn is_stamp(&self) -> bool {
+        self.banners > 0 && (self.content.trim_ascii_start().starts_with(b"Q") || !self.draws_more)
+    }
+
+    fn draws(&self) -> bool {
+        self.text > 0 || self.xobjects > 0 || self.paints
+    }
+
+    /// Whether the stream shows the few lines of text of a premium overlay.
+    fn shows_overlay_text(&self) -> bool {
+        self.text <= MAX_OVERLAY_TEXT
+            && OVERLAY_MARKERS
+                .iter()
+                .any(|marker| contains(&self.shown, marker))
+    }
+
+    /// Whether the stream fills a rectangle over most of the page, like the veil of an overlay.
+    fn fills_page(&self, page_box: &Rect) -> bool {
+        self.fills
+            .iter()
+            .any(|rect| covers(rect, page_box, PREVIEW_COVERAGE))
+    }
+
+    /// Whether the stream only draws images, one of which covers most of the page.
+    fn is_preview(
+        &self,
+        doc: &Document,
+        page: &ObjectId,
+        page_box: &Rect,
+        budget: &Budget,
+    ) -> bool {
+        if self.text > 0 || self.paints || self.xobjects == 0 {
+            return false;
+        }
+        content_image_placements(doc, page, &self.content, budget)
+            .unwrap_or_default()
+            .iter()
+            .any(|placement| covers(&placement.rect, page_box, PREVIEW_COVERAGE))
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|x| x == needle)
+}
+
+/// The text shown by a text showing operator.
+fn shown_text(operation: &Operation) -> Vec<u8> {
+    let mut text = Vec::new();
+    for operand in &operation.operands {
+        match operand {
+            Object::String(bytes, _) => text.extend_from_slice(bytes),
+            // Kerned text is split into several strings.
+            Object::Array(parts) => {
+                for part in parts {
+                    if let Object::String(bytes, _) = part {
+                        text.extend_from_slice(bytes);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// A text object that draws a StuDocu banner.
+struct BannerBlock {
+    /// The operations from `BT` to `ET`.
+    range: Range<usize>,
+    /// Whether it prints the StuDocu identifier.
+    identifier: bool,
+}
+
+/// The text objects, from `BT` to `ET`, that draw a StuDocu banner.
+///
+/// A banner is a short line in a small font that says what StuDocu writes, so that text of the
+/// original document that quotes it is left alone.
+fn banner_blocks(operations: &[Operation]) -> Vec<BannerBlock> {
+    let mut blocks = Vec::new();
+    let mut start = None;
+    let mut text = Vec::new();
+    let mut texts = 0;
+    // The font size is part of the text state, which carries over from one text object to the
+    // next. A banner shows all of its text in a small font.
+    let mut font_size = 0.0;
+    let mut largest = 0.0;
+    for (i, operation) in operations.iter().enumerate() {
+        match operation.operator.as_str() {
+            "BT" => {
+                start = Some(i);
+                text.clear();
+                texts = 0;
+                largest = 0.0;
+            }
+            "Tf" => {
+                if let Some(size) = operation.operands.get(1).and_then(|x| as_number(x).ok()) {
+                    font_size = size.abs();
+                }
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                text.extend(shown_text(operation));
+                texts += 1;
+                largest = f32::max(largest, font_size);
+            }
+            "ET" => {
+                let lowercase = text.to_ascii_lowercase();
+                if let Some(start) = start.take() {
+                    if texts <= MAX_BANNER_TEXTS
+                        && largest <= MAX_BANNER_FONT_SIZE
+                        && BANNER_MARKERS
+                            .iter()
+                            .any(|marker| contains(&lowercase, marker))
+                    {
+                        blocks.push(BannerBlock {
+                            range: start..i + 1,
+                            identifier: contains(&lowercase, IDENTIFIER),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Removes the text objects that draw StuDocu banners from a content stream.
+///
+/// Returns `None` if the stream draws no banners, or cannot be parsed.
+pub fn strip_banners(content: &[u8]) -> Option<Vec<u8>> {
+    let operations = Content::decode(content).ok()?.operations;
+    let banners = banner_blocks(&operations);
+    if banners.is_empty() {
+        return None;
+    }
+    let operations: Vec<Operation> = operations
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !banners.iter().any(|banner| banner.range.contains(i)))
+        .map(|(_, operation)| operation)
+        .collect();
+    Content { operations }.encode().ok()
+}
+
+/// Works out which streams of a page StuDocu added, from what they draw.
+///
+/// Returns `None` for pages that do not print the StuDocu identifier, like pages the uploader
+/// added to the document after it was served, or documents that only quote what the banners
+/// say.
+pub fn studocu_layout(
+    doc: &Document,
+    page: &ObjectId,
+    contents: &[ObjectId],
+    budget: &Budget,
+) -> Option<StuDocuLayout> {
+    let streams: Vec<PageStream> = contents
+        .iter()
+        .filter_map(|id| PageStream::new(doc, *id))
+        .collect();
+    if !streams.iter().any(|x| x.identified) {
+        return None;
+    }
+    let stamps: Vec<&[u8]> = streams
+        .iter()
+        .filter(|x| x.is_stamp())
+        .map(|x| x.content.as_slice())
+        .collect();
+
+    // The streams that only save the graphics state are dropped along with the stamps that
+    // restore it, so that what is kept stays balanced.
+    let restores = streams
+        .iter()
+        .filter(|x| x.is_stamp() && x.content.trim_ascii_start().starts_with(b"Q"))
+        .count();
+    let mut saves = 0;
+    let (added, rest): (Vec<&PageStream>, Vec<&PageStream>) = streams.iter().partition(|x| {
+        if x.is_stamp() {
+            return true;
+        }
+        let save = x.content.trim_ascii() == b"q" && saves < restores;
+        saves += save as usize;
+        save
+    });
+    // The stamp of the cover draws the cover again, so every stream of the cover is repeated in
+    // it. The streams of a real page are never part of its stamp.
+    let cover = !stamps.is_empty()
+        && rest.iter().all(|x| {
+            let content = x.content.trim_ascii();
+            content.is_empty() || stamps.iter().any(|stamp| contains(stamp, content))
+        });
+
+    // The text of an overlay is only trusted over a blurred preview or a veil over the whole
+    // page, as the original pages may say the same. Blurred previews are only looked for under
+    // that text, as scanned pages also draw a single image over the whole page.
+    let mut overlays: Vec<ObjectId> = Vec::new();
+    if rest.iter().any(|x| x.shows_overlay_text()) {
+        if let Ok(page_box) = media_box(doc, page) {
+            let previews: Vec<ObjectId> = rest
+                .iter()
+                .filter(|x| x.is_preview(doc, page, &page_box, budget))
+                .map(|x| x.id)
+                .collect();
+            overlays = rest
+                .iter()
+                .filter(|x| x.shows_overlay_text())
+                .filter(|x| !previews.is_empty() || x.fills_page(&page_box))
+                .map(|x| x.id)
+                .collect();
+            if !overlays.is_empty() {
+                overlays.extend(previews);
+            }
+        }
+    }
+    let underneath: Vec<ObjectId> = rest
+        .iter()
+        .filter(|x| !overlays.contains(&x.id))
+        .map(|x| x.id)
+        .collect();
+    let incomplete = !cover
+        && !overlays.is_empty()
+        && !rest.iter().any(|x| underneath.contains(&x.id) && x.draws());
+
+    Some(StuDocuLayout {
+        content: if incomplete {
+            rest.iter().map(|x| x.id).collect()
+        } else {
+            underneath
+        },
+        added: added.iter().map(|x| x.id).collect(),
+        banners: rest
+            .iter()
+            .filter(|x| x.banners > 0)
+            .map(|x| x.id)
+            .collect(),
+        overlays,
+        cover,
+        incomplete,
+    })
+}
@@ -0,0 +1,243 @@
+use std::error::Error;
+
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, ObjectId, Stream,
+};
+
+use crate::{
+    limits::Budget,
+    models::{
+        geometry::{content_image_placements, image_placements, media_box, ImagePlacement, Rect},
+        method::Method,
+        plan::{PageAction, PagePlan},
+        resources::page_resources,
+    },
+};
+
+/// The resource name of the font used to stamp the pages.
+const PREVIEW_FONT: &str = "GulagCleanerPreview";
+
+/// Outline colour of the regions that are removed.
+const REMOVED: [f32; 3] = [0.9, 0.0, 0.0];
+/// Outline colour of the region that is kept after cropping.
+const KEPT: [f32; 3] = [0.0, 0.6, 0.0];
+/// Outline colour of pages whose contents are replaced.
+const REPLACED: [f32; 3] = [1.0, 0.5, 0.0];
+/// Outline colour of the logos that are hidden.
+const LOGO: [f32; 3] = [0.0, 0.3, 1.0];
+
+/// Draws the plan of every page over the original document, without removing anything.
+///
+/// Every page is kept. Regions that would be cropped or deleted are outlined in red, the
+/// region kept by a crop in green, pages whose contents would be replaced in orange and
+/// hidden logos in blue. The images drawn by deleted pages and by the streams that replacing
+/// the contents drops are outlined in red as well. The text and paths of those streams, and the
+/// banners removed from the kept streams, are only marked by the outline of their page. Each
+/// page is also stamped with its page type and planned action.
+pub fn render_preview(
+    doc: &mut Document,
+    method: &Method,
+    plans: &[PagePlan],
+    budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+
+    for plan in plans {
+        let Ok(page_box) = media_box(doc, &plan.id) else {
+            continue;
+        };
+        let mut operations = vec![Operation::new("q", vec![])];
+
+        match &plan.action {
+            PageAction::Keep => {}
+            PageAction::Delete => outline(&mut operations, inset(page_box, 2.0), REMOVED, 4.0),
+            PageAction::Crop(rect) => outline_crop(&mut operations, page_box, *rect),
+            PageAction::Rescale { scale, rect } => {
+                // The kept region is given in the scaled coordinates of the new contents.
+                let kept = rect.map(|x| x / scale);
+                outline_crop(&mut operations, page_box, kept);
+            }
+            PageAction::ReplaceContents { rect, .. } => {
+                outline(&mut operations, inset(page_box, 2.0), REPLACED, 4.0);
+                if let Some(rect) = rect {
+                    outline_crop(&mut operations, page_box, *rect);
+                }
+            }
+        }
+
+        for placement in image_placements(doc, &plan.id, budget)? {
+            if plan.logos.contains(&placement.id) {
+                outline(&mut operations, placement.rect, LOGO, 1.5);
+            } else if plan.action == PageAction::Delete {
+                outline(&mut operations, placement.rect, REMOVED, 1.5);
+            }
+        }
+        if let PageAction::ReplaceContents { contents, .. } = &plan.action {
+            for placement in dropped_images(doc, plan.id, contents, budget)? {
+                outline(&mut operations, placement.rect, REMOVED, 1.5);
+            }
+        }
+
+        let label = format!(
+            "{} | page {}: {:?} -> {}{}",
+            method.name(),
+            plan.number,
+            plan.page_type,
+            plan.action.describe(),
+            if plan.incomplete { " (incomplete)" } else { "" }
+        );
+        stamp(&mut operations, page_box, &label);
+        operations.push(Operation::new("Q", vec![]));
+
+        let overlay = Content { operations }.encode()?;
+        add_preview_font(doc, plan.id, font_id)?;
+        wrap_page_contents(doc, plan.id, overlay)?;
+    }
+    Ok(())
+}
+
+/// The images drawn by the streams of a page that are not among the `kept` ones.
+fn dropped_images(
+    doc: &Document,
+    page: ObjectId,
+    kept: &[ObjectId],
+    budget: &Budget,
+) -> Result<Vec<ImagePlacement>, Box<dyn Error>> {
+    let mut placements = Vec::new();
+    for id in doc.get_page_contents(page) {
+        if kept.contains(&id) {
+            continue;
+        }
+        let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+            continue;
+        };
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        placements.extend(content_image_placements(doc, &page, &content, budget)?);
+    }
+    Ok(placements)
+}
+
+fn inset([llx, lly, urx, ury]: Rect, by: f32) -> Rect {
+    [llx + by, lly + by, urx - by, ury - by]
+}
+
+fn outline(
+    operations: &mut Vec<Operation>,
+    [llx, lly, urx, ury]: Rect,
+    color: [f32; 3],
+    width: f32,
+) {
+    if urx <= llx || ury <= lly {
+        return;
+    }
+    operations.extend([
+        Operation::new("w", vec![width.into()]),
+        Operation::new("RG", color.iter().map(|&x| x.into()).collect()),
+        Operation::new(
+            "re",
+            vec![
+                llx.into(),
+                lly.into(),
+                (urx - llx).into(),
+                (ury - lly).into(),
+            ],
+        ),
+        Operation::new("S", vec![]),
+    ]);
+}
+
+/// Outlines the region kept by a crop, and the strips of the page that are cut away around it.
+fn outline_crop(operations: &mut Vec<Operation>, page_box: Rect, kept: Rect) {
+    let [llx, lly, urx, ury] = page_box;
+    let [kllx, klly, kurx, kury] = [
+        kept[0].clamp(llx, urx),
+        kept[1].clamp(lly, ury),
+        kept[2].clamp(llx, urx),
+        kept[3].clamp(lly, ury),
+    ];
+    for strip in [
+        [llx, lly, kllx, ury],
+        [kurx, lly, urx, ury],
+        [kllx, lly, kurx, klly],
+        [kllx, kury, kurx, ury],
+    ] {
+        outline(operations, strip, REMOVED, 2.0);
+    }
+    outline(operations, [kllx, klly, kurx, kury], KEPT, 2.0);
+}
+
+/// Writes a label on a white background at the top left corner of the page.
+fn stamp(operations: &mut Vec<Operation>, [llx, _, _, ury]: Rect, label: &str) {
+    let size = 9.0;
+    let (x, y) = (llx + 8.0, ury - 8.0 - size);
+    // Helvetica glyphs are a bit over half an em wide on average.
+    let width = label.len() as f32 * size * 0.55;
+    operations.extend([
+        Operation::new("rg", vec![1.into(), 1.into(), 1.into()]),
+        Operation::new(
+            "re",
+            vec![
+                (x - 2.0).into(),
+                (y - 3.0).into(),
+                (width + 4.0).into(),
+                (size + 4.0).into(),
+            ],
+        ),
+        Operation::new("f", vec![]),
+        Operation::new("BT", vec![]),
+        Operation::new("rg", REMOVED.iter().map(|&x| x.into()).collect()),
+        Operation::new("Tf", vec![Object::Name(PREVIEW_FONT.into()), size.into()]),
+        Operation::new("Td", vec![x.into(), y.into()]),
+        Operation::new("Tj", vec![Object::string_literal(label)]),
+        Operation::new("ET", vec![]),
+    ]);
+}
+
+/// Gives a page its own copy of its resources, with the preview font added to them.
+fn add_preview_font(
+    doc: &mut Document,
+    page: ObjectId,
+    font_id: ObjectId,
+) -> Result<(), Box<dyn Error>> {
+    let mut resources = page_resources(doc, &page)?.cloned().unwrap_or_default();
+    let mut fonts = resources
+        .get(b"Font")
+        .and_then(|x| doc.dereference(x))
+        .and_then(|x| x.1.as_dict())
+        .cloned()
+        .unwrap_or_default();
+    fonts.set(PREVIEW_FONT, font_id);
+    resources.set("Font", fonts);
+    doc.get_dictionary_mut(page)?.set("Resources", resources);
+    Ok(())
+}
+
+/// Isolates the original contents of a page in a `q`/`Q` pair and draws the overlay after them.
+fn wrap_page_contents(
+    doc: &mut Document,
+    page: ObjectId,
+    overlay: Vec<u8>,
+) -> Result<(), Box<dyn Error>> {
+    let mut contents: Vec<Object> = doc
+        .get_page_contents(page)
+        .into_iter()
+        .map(Object::Reference)
+        .collect();
+    let prefix = doc.add_object(Stream::new(dictionary! {}, b"q\n".to_vec()));
+    let suffix = doc.add_object(Stream::new(
+        dictionary! {},
+        [b"\nQ\n".to_vec(), overlay].concat(),
+    ));
+    contents.insert(0, prefix.into());
+    contents.push(suffix.into());
+    doc.get_dictionary_mut(page)?.set("Contents", contents);
+    Ok(())
+}
@@ -1,14 +1,15 @@
-use crate::clean::clean_pdf;
 use std::fs;
-use std::time::Instant;
 
-const OUT_PATH: &str = "example_docs/out";
+/// Synthetic documents mimicking the layout of each source.
+mod fixtures;
 
-/// Represents configuration for running a test, including the paths for input and output files.
-struct TestConfig {
-    input_path: &'static str,
-    output_filename: &'static str,
-}
+/// Data-driven regression tests over the sample documents.
+mod golden;
+
+/// Malformed documents and documents that exceed the limits.
+mod hostile;
+
+const OUT_PATH: &str = "example_docs/out";
 
 /// Ensures the output directory exists, creating it if necessary.
 /// This function is invoked before running tests to ensure a location
@@ -17,178 +18,1160 @@ fn create_output_directory() {
     fs::create_dir_all(OUT_PATH).expect("Failed to create output directory");
 }
 
-/// Reads a PDF from the specified path, cleans it, and returns the cleaned PDF data.
-///
-/// # Arguments
-///
-/// * `in_path` - A string slice that holds the path to the input PDF file.
-///
-/// # Returns
-///
-/// A `Result` which is `Ok` with a `Vec<u8>` containing the cleaned PDF data,
-/// or an `Err` with a string describing the error.
-fn read_and_clean_pdf(in_path: &str) -> Result<Vec<u8>, String> {
-    let data =
-        std::fs::read(in_path).map_err(|e| format!("Failed to read `{in_path}`: {e}"))?;
-    let (clean_file, _) = clean_pdf(data, false);
-    Ok(clean_file)
-}
-
-/// Writes the cleaned PDF data to a file in the output directory.
-///
-/// # Arguments
-///
-/// * `out_path` - The path where the cleaned PDF will be stored.
-/// * `clean_file` - A vector of bytes representing the cleaned PDF data.
-///
-/// # Returns
-///
-/// A `Result` which is `Ok` if the file was successfully written, or an `Err`
-/// with a string describing the error.
-fn store_pdf(out_path: &str, clean_file: Vec<u8>) -> Result<(), String> {
-    std::fs::write(out_path, clean_file)
-        .map_err(|e| format!("Failed to write `{out_path}`: {e}"))
-}
-
-/// Executes a cleaning test using the provided `TestConfig`.
-///
-/// This function orchestrates the test process: creating the output directory,
-/// cleaning the PDF specified in the `TestConfig`, and storing the cleaned PDF
-/// in the output directory. It also measures and prints the duration of the test.
-///
-/// # Arguments
-///
-/// * `test_config` - A reference to the `TestConfig` containing the test parameters.
-fn run_test_for_config(test_config: &TestConfig) {
-    create_output_directory();
-
-    let start = Instant::now();
+/// Builds a single-page document whose only image sits inside two nested Form XObjects,
+/// with the inner form also referencing the outer one to form a cycle. The page inherits
+/// its resources from the page tree root.
+fn nested_forms_document() -> (lopdf::Document, lopdf::ObjectId, lopdf::ObjectId, Vec<lopdf::ObjectId>) {
+    use lopdf::{dictionary, Document, Object, Stream};
 
-    let clean_file = read_and_clean_pdf(test_config.input_path).expect("Failed to clean PDF");
-    store_pdf(
-        &format!("{}/{}", OUT_PATH, test_config.output_filename),
-        clean_file,
-    )
-    .expect("Failed to store PDF");
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let outer_id = doc.new_object_id();
+    let inner_id = doc.new_object_id();
 
-    let duration = start.elapsed();
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Height" => 71,
+            "Width" => 390,
+        },
+        vec![],
+    ));
+    doc.objects.insert(
+        inner_id,
+        Object::Stream(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "Resources" => dictionary! {
+                    "XObject" => dictionary! { "Im0" => image_id, "Fm0" => outer_id },
+                },
+            },
+            b"/Im0 Do".to_vec(),
+        )),
+    );
+    doc.objects.insert(
+        outer_id,
+        Object::Stream(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "Resources" => dictionary! { "XObject" => dictionary! { "Fm1" => inner_id } },
+            },
+            b"/Fm1 Do".to_vec(),
+        )),
+    );
 
-    println!(
-        "Test for `{}` completed in {:?}",
-        test_config.input_path, duration
+    let content_id = doc.add_object(Stream::new(dictionary! {}, b"/Fm0 Do".to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Resources" => dictionary! { "XObject" => dictionary! { "Fm0" => outer_id } },
+        }),
     );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+
+    (doc, page_id, image_id, vec![outer_id, inner_id])
 }
 
-// Define tests for specific PDF files, utilizing the TestConfig structure.
+#[test]
+fn test_collect_images_in_nested_forms() {
+    use crate::models::resources::collect_page_images;
+
+    let (doc, page_id, image_id, forms) = nested_forms_document();
+    let images = collect_page_images(&doc, &page_id).expect("Failed to collect images");
+
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].id, image_id);
+    assert_eq!(images[0].dims(), (71, 390));
+    assert_eq!(images[0].forms, forms);
+}
 
 #[test]
-fn test_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-free-example.pdf",
-        output_filename: "wuolah_clean.pdf",
-    });
+fn test_remove_logo_in_nested_forms() {
+    use crate::models::method::remove_logo;
+
+    let (mut doc, page_id, image_id, _) = nested_forms_document();
+    remove_logo(&mut doc, &page_id).expect("Failed to remove logo");
+
+    let image = doc.get_object(image_id).unwrap().as_stream().unwrap();
+    assert_eq!(image.dict.get(b"Height").unwrap().as_i64().unwrap(), 0);
 }
 
 #[test]
-fn test_010624_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-010624-example.pdf",
-        output_filename: "wuolah_010624_clean.pdf",
+fn test_page_resources_are_inherited_as_a_whole() {
+    use crate::models::{geometry::image_placements, resources::collect_page_images};
+    use lopdf::{dictionary, Object};
+
+    // Resources on the page tree apply to the pages that have none of their own.
+    let (mut doc, page_id) = single_image_document(4, 4, vec![0; 16]);
+    let page = doc.get_dictionary_mut(page_id).unwrap();
+    let resources = page.remove(b"Resources").unwrap();
+    let pages_id = page.get(b"Parent").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(pages_id)
+        .unwrap()
+        .set("Resources", resources);
+    assert_eq!(collect_page_images(&doc, &page_id).unwrap().len(), 1);
+    assert_eq!(image_placements(&doc, &page_id, &Default::default()).unwrap().len(), 1);
+
+    // But they are not merged with the resources of a page that has them.
+    doc.get_dictionary_mut(page_id)
+        .unwrap()
+        .set("Resources", dictionary! { "Font" => Object::Dictionary(dictionary! {}) });
+    assert!(collect_page_images(&doc, &page_id).unwrap().is_empty());
+    assert!(image_placements(&doc, &page_id, &Default::default()).unwrap().is_empty());
+}
+
+/// Builds a single-page document drawing one 8-bit grayscale image with the given pixels.
+fn single_image_document(height: i64, width: i64, pixels: Vec<u8>) -> (lopdf::Document, lopdf::ObjectId) {
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Height" => height,
+            "Width" => width,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        pixels,
+    ));
+    let content_id = doc.add_object(Stream::new(dictionary! {}, b"/Im0 Do".to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
     });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+
+    (doc, page_id)
+}
+
+fn gradient(height: i64, width: i64, noise: u8) -> Vec<u8> {
+    (0..height * width)
+        .map(|i| (255 - (i % width) * 255 / width) as u8 ^ (noise * (i % 7 == 0) as u8))
+        .collect()
 }
 
 #[test]
-fn test_170924_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-170924-example.pdf",
-        output_filename: "wuolah-170924-example_clean.pdf",
-    });
+fn test_hashed_signatures_ignore_same_size_images() {
+    use crate::models::{
+        page_type::PageType,
+        resources::collect_page_images,
+        signature::{AdSignature, AdSignatures},
+    };
+
+    let (ad_doc, ad_page) = single_image_document(1754, 1240, gradient(1754, 1240, 0));
+    let ad_image = &collect_page_images(&ad_doc, &ad_page).unwrap()[0];
+    let signatures = AdSignatures {
+        full_page: vec![AdSignature::from_image(&ad_doc, ad_image)],
+        ..Default::default()
+    };
+
+    // A full-page scan of the same size is an ad for the size-only signatures, but not for the hashed ones.
+    let (scan_doc, scan_page) = single_image_document(1754, 1240, vec![255; 1754 * 1240]);
+    assert!(matches!(
+        PageType::get_page_type(&scan_doc, &scan_page).unwrap(),
+        PageType::FullPageAds
+    ));
+    assert!(matches!(
+        PageType::get_page_type_with(&scan_doc, &scan_page, &signatures).unwrap(),
+        PageType::Idk
+    ));
+
+    // A re-encoded copy of the creative still matches through its perceptual hash.
+    let (copy_doc, copy_page) = single_image_document(1754, 1240, gradient(1754, 1240, 1));
+    let copy_image = &collect_page_images(&copy_doc, &copy_page).unwrap()[0];
+    assert!(!signatures.full_page[0]
+        .clone()
+        .with_perceptual_hash(0)
+        .matches(&copy_doc, copy_image));
+    assert!(matches!(
+        PageType::get_page_type_with(&copy_doc, &copy_page, &signatures).unwrap(),
+        PageType::FullPageAds
+    ));
 }
 
 #[test]
-fn test_280924_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-280924-example.pdf",
-        output_filename: "wuolah-280924-example_clean.pdf",
-    });
+fn test_preview_keeps_every_page() {
+    use crate::clean::{clean_pdf_with_options, CleanOptions};
+    use lopdf::Document;
+
+    let data = fs::read("example_docs/wuolah-031024-example.pdf").expect("Failed to read PDF");
+    let original_pages = Document::load_mem(&data).unwrap().get_pages().len();
+    let (preview, method) = clean_pdf_with_options(
+        data,
+        &CleanOptions {
+            preview: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(method, 2);
+
+    let preview = Document::load_mem(&preview).expect("Failed to load preview");
+    let pages = preview.get_pages();
+    assert_eq!(pages.len(), original_pages);
+    for (number, id) in pages {
+        let text = String::from_utf8_lossy(&preview.get_page_content(id).unwrap()).to_string();
+        assert!(text.contains("/GulagCleanerPreview"));
+        assert!(text.contains(&format!("Naive | page {number}: ")));
+    }
 }
 
 #[test]
-fn test_280924_2_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-280924-2-example.pdf",
-        output_filename: "wuolah-280924-2-example_clean.pdf",
-    });
+fn test_preview_outlines_removed_images() {
+    use crate::clean::{clean_pdf_report, CleanOptions};
+    use fixtures::{docsity_document, studocu_document, DocsityPage, StuDocuPage};
+    use lopdf::{content::Content, Document};
+
+    // The rectangles stroked in the colour and width of the removed images.
+    let removed = |doc: &Document, page: u32| -> Vec<Vec<f32>> {
+        let id = doc.get_pages()[&page];
+        let content = Content::decode(&doc.get_page_content(id).unwrap()).unwrap();
+        let (mut width, mut color, mut path) = (0.0, Vec::new(), Vec::new());
+        let mut rects = Vec::new();
+        for operation in content.operations {
+            let numbers: Vec<f32> = operation
+                .operands
+                .iter()
+                .filter_map(|x| x.as_float().ok())
+                .collect();
+            match operation.operator.as_str() {
+                "w" => width = numbers[0],
+                "RG" => color = numbers,
+                "re" => path = numbers,
+                "S" if width == 1.5 && color == [0.9, 0.0, 0.0] => rects.push(path.clone()),
+                _ => {}
+            }
+        }
+        rects
+    };
+    let preview = |mut doc: Document| {
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+        let options = CleanOptions {
+            preview: true,
+            ..Default::default()
+        };
+        let (preview, _) = clean_pdf_report(&data, &options).unwrap();
+        Document::load_mem(&preview).unwrap()
+    };
+    let a4 = [0.0, 0.0, 595.0, 842.0];
+
+    // The cover of a Docsity document is deleted, and the blurred preview of a StuDocu page is
+    // dropped with the streams StuDocu added.
+    let doc = preview(docsity_document(&[DocsityPage::Cover, DocsityPage::Content]));
+    assert_eq!(removed(&doc, 1), [a4]);
+    let doc = preview(studocu_document(&[
+        StuDocuPage::Cover,
+        StuDocuPage::Overlay,
+        StuDocuPage::Content,
+    ]));
+    assert_eq!(removed(&doc, 2), [a4]);
+    assert!(removed(&doc, 3).is_empty());
 }
 
 #[test]
-fn test_300924_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-300924-example.pdf",
-        output_filename: "wuolah-300924-example_clean.pdf",
-    });
+fn test_verify_wuolah_keeps_content() {
+    use crate::clean::{clean_pdf_report, CleanOptions};
+    use crate::verify::Verdict;
+
+    let data = fs::read("example_docs/wuolah-170924-example.pdf").expect("Failed to read PDF");
+    let (_, report) = clean_pdf_report(
+        &data,
+        &CleanOptions {
+            verify: true,
+            ..Default::default()
+        },
+    )
+    .expect("Failed to clean PDF");
+    assert_eq!(report.method_code, 0);
+
+    let verification = report.verification.expect("Verification was not run");
+    assert_eq!(verification.pages.len(), report.pages.len());
+    assert_ne!(verification.verdict, Verdict::Fail);
+    for page in verification.pages.iter().filter(|x| x.cleaned_number.is_some()) {
+        assert_eq!(page.verdict, Verdict::Pass, "{page:?}");
+    }
 }
 
 #[test]
-fn test_300924_2_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-300924-2-example.pdf",
-        output_filename: "wuolah-300924-2-example_clean.pdf",
+fn test_verify_flags_lost_text() {
+    use crate::models::{
+        page_type::PageType,
+        plan::{PageAction, PagePlan},
+        signature::AdSignatures,
+    };
+    use crate::verify::{verify, Verdict};
+    use lopdf::{content::Content, dictionary, Document, Object, Stream};
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
     });
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(5);
+    let content = Content {
+        operations: vec![
+            lopdf::content::Operation::new("BT", vec![]),
+            lopdf::content::Operation::new("Tf", vec!["F1".into(), 12.into()]),
+            lopdf::content::Operation::new("Td", vec![50.into(), 700.into()]),
+            lopdf::content::Operation::new("Tj", vec![Object::string_literal(text)]),
+            lopdf::content::Operation::new("ET", vec![]),
+        ],
+    };
+    let kids: Vec<Object> = (0..2)
+        .map(|_| {
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            })
+            .into()
+        })
+        .collect();
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => 2,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+
+    let plans: Vec<PagePlan> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(number, id)| PagePlan {
+            number,
+            id,
+            page_type: PageType::Idk,
+            action: PageAction::Keep,
+            logos: vec![],
+            banners: vec![],
+            incomplete: false,
+        })
+        .collect();
+    let signatures = AdSignatures::default();
+
+    let untouched = verify(&doc, &doc.clone(), &plans, &signatures);
+    assert_eq!(untouched.verdict, Verdict::Pass);
+
+    // Blank the first page while keeping it.
+    let mut cleaned = doc.clone();
+    cleaned.change_page_content(plans[0].id, vec![]).unwrap();
+    let blanked = verify(&doc, &cleaned, &plans, &signatures);
+    assert_eq!(blanked.verdict, Verdict::Fail);
+    assert_eq!(blanked.pages[0].verdict, Verdict::Fail);
+    assert_eq!(blanked.pages[1].verdict, Verdict::Pass);
+
+    // Delete the second page, which has some text but not much.
+    let mut cleaned = doc.clone();
+    cleaned.delete_pages(&[2]);
+    let mut plans = plans;
+    plans[1].action = PageAction::Delete;
+    let deleted = verify(&doc, &cleaned, &plans, &signatures);
+    assert_eq!(deleted.pages[1].verdict, Verdict::Warn);
+    assert_eq!(deleted.verdict, Verdict::Warn);
 }
 
 #[test]
-fn test_031024_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-031024-example.pdf",
-        output_filename: "wuolah-031024-example_clean.pdf",
-    });
+fn test_clean_reader_matches_clean_pdf() {
+    use crate::clean::{clean_pdf, CleanOptions};
+    use crate::io::clean_reader;
+
+    let path = "example_docs/wuolah-170924-example.pdf";
+    let (expected, method) =
+        clean_pdf(fs::read(path).expect("Failed to read PDF"), false).unwrap();
+
+    let mut output = Vec::new();
+    let report = clean_reader(
+        fs::File::open(path).expect("Failed to open PDF"),
+        &mut output,
+        &CleanOptions::default(),
+    )
+    .expect("Failed to clean PDF");
+    assert_eq!(report.method_code, method);
+    assert_eq!(output, expected);
 }
 
+#[cfg(feature = "fs")]
 #[test]
-fn test_031024_2_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-031024-2-example.pdf",
-        output_filename: "wuolah-031024-2-example_clean.pdf",
-    });
+fn test_clean_path_replaces_in_place() {
+    use crate::clean::{clean_pdf, CleanOptions};
+    use crate::io::clean_path;
+
+    create_output_directory();
+    let path = format!("{OUT_PATH}/in_place.pdf");
+    let data = fs::read("example_docs/wuolah-170924-example.pdf").expect("Failed to read PDF");
+    fs::write(&path, &data).expect("Failed to copy PDF");
+
+    let report = clean_path(&path, &path, &CleanOptions::default()).expect("Failed to clean PDF");
+    assert_eq!(report.method_code, 0);
+    assert_eq!(fs::read(&path).unwrap(), clean_pdf(data, false).unwrap().0);
+
+    // A failed clean leaves the output as it was, and no temporary files behind.
+    let broken = format!("{OUT_PATH}/broken.pdf");
+    fs::write(&broken, b"not a pdf").unwrap();
+    let before = fs::read(&path).unwrap();
+    assert!(clean_path(&broken, &path, &CleanOptions::default()).is_err());
+    assert_eq!(fs::read(&path).unwrap(), before);
+    assert!(!fs::read_dir(OUT_PATH)
+        .unwrap()
+        .any(|x| x.unwrap().file_name().to_string_lossy().starts_with(".tmp")));
 }
 
 #[test]
-fn test_041024_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-041024-example.pdf",
-        output_filename: "wuolah-041024-example_clean.pdf",
-    });
+fn test_batch_keeps_going_after_a_bad_file() {
+    use crate::batch::clean_batch_bytes;
+    use crate::clean::{clean_pdf, CleanOptions};
+
+    let wuolah = fs::read("example_docs/wuolah-170924-example.pdf").expect("Failed to read PDF");
+    let studocu = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let documents = vec![
+        wuolah.clone(),
+        b"not a pdf".to_vec(),
+        studocu.clone(),
+        wuolah.clone(),
+    ];
+
+    let batch = clean_batch_bytes(documents, &CleanOptions::default(), 2);
+    assert_eq!(batch.files.len(), 4);
+    assert!(batch.files[1].1.is_err());
+    assert_eq!(batch.files[0].0, clean_pdf(wuolah.clone(), false).unwrap().0);
+    assert_eq!(batch.files[2].0, clean_pdf(studocu.clone(), false).unwrap().0);
+    assert_eq!(batch.files[0].0, batch.files[3].0);
+
+    let summary = &batch.summary;
+    assert_eq!((summary.cleaned, summary.failed), (3, 1));
+    assert_eq!(summary.methods.get("Wuolah"), Some(&2));
+    assert_eq!(summary.methods.get("StuDocu"), Some(&1));
+    assert_eq!(
+        summary.input_bytes,
+        (wuolah.len() * 2 + studocu.len()) as u64
+    );
+    let output_bytes: usize = batch.files.iter().map(|x| x.0.len()).sum();
+    assert_eq!(summary.output_bytes, output_bytes as u64);
+    assert_eq!(
+        summary.bytes_saved(),
+        summary.input_bytes as i64 - output_bytes as i64
+    );
 }
 
+#[cfg(feature = "watch")]
 #[test]
-fn test_061024_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-061024-example.pdf",
-        output_filename: "wuolah-061024-example_clean.pdf",
-    });
+fn test_watch_cleans_each_file_once() {
+    use crate::watch::{FolderWatcher, OutputLocation, WatchOptions};
+    use std::time::{Duration, Instant};
+
+    let dir = format!("{OUT_PATH}/watch");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(format!("{dir}/inbox")).unwrap();
+    let input = format!("{dir}/inbox/notes.pdf");
+    fs::copy("example_docs/wuolah-170924-example.pdf", &input).unwrap();
+
+    let mut options = WatchOptions::new(vec![format!("{dir}/inbox").into()]);
+    options.output = OutputLocation::Directory(format!("{dir}/clean").into());
+    options.journal = format!("{dir}/journal").into();
+    options.settle = Duration::from_secs(60);
+
+    // Files are only cleaned once they stop changing for the settle time.
+    let mut watcher = FolderWatcher::new(options.clone()).unwrap();
+    let start = Instant::now();
+    watcher.scan(start).unwrap();
+    assert!(watcher.process_ready(start).is_empty());
+
+    let events = watcher.process_ready(start + Duration::from_secs(61));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].result.as_ref().unwrap(), &"Wuolah");
+    assert!(fs::metadata(format!("{dir}/clean/notes.pdf")).is_ok());
+
+    // The outputs are not picked up, and processed files are not cleaned again, even after a
+    // restart.
+    let later = start + Duration::from_secs(200);
+    watcher.scan(later).unwrap();
+    assert!(watcher.process_ready(later).is_empty());
+    let mut watcher = FolderWatcher::new(options).unwrap();
+    watcher.scan(start).unwrap();
+    assert!(watcher.process_ready(later).is_empty());
+
+    // A file that changes is cleaned again.
+    fs::copy("example_docs/studocu-example.pdf", &input).unwrap();
+    watcher.scan(start).unwrap();
+    let events = watcher.process_ready(later);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].result.as_ref().unwrap(), &"StuDocu");
 }
 
 #[test]
-fn test_191024_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-191024-example.pdf",
-        output_filename: "wuolah-191024-example_clean.pdf",
-    });
+fn test_metadata_reads_cover_lines() {
+    use crate::metadata::{extract_metadata, Metadata};
+
+    let data = fs::read("example_docs/wuolah-170924-example.pdf").expect("Failed to read PDF");
+    assert_eq!(
+        extract_metadata(&data).unwrap(),
+        Metadata {
+            file: Some("Tema1-2.pdf".to_string()),
+            author: Some("Anónimo".to_string()),
+            subject: Some("Biología de Sistemas".to_string()),
+            course: Some("4º Grado en Ingeniería de la Salud".to_string()),
+            faculty: Some("Escuela Técnica Superior de Ingeniería Informática".to_string()),
+            university: Some("Universidad de Málaga".to_string()),
+        }
+    );
+
+    // Newer covers draw every detail in a single text object.
+    let data = fs::read("example_docs/wuolah-191024-example.pdf").expect("Failed to read PDF");
+    let metadata = extract_metadata(&data).unwrap();
+    assert_eq!(metadata.author.as_deref(), Some("user_3068822"));
+    assert_eq!(metadata.subject.as_deref(), Some("Bioquímica"));
+    assert_eq!(
+        metadata.university.as_deref(),
+        Some("Universidad CEU Cardenal Herrera")
+    );
+
+    // StuDocu documents have no text cover.
+    let data = fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    assert_eq!(extract_metadata(&data).unwrap(), Metadata::default());
 }
 
 #[test]
-fn test_281124_wuolah_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/wuolah-281124-example.pdf",
-        output_filename: "wuolah-281124-example_clean.pdf",
+fn test_encrypted_pdf_is_reported() {
+    use crate::error::CleanError;
+    use lopdf::dictionary;
+
+    let (mut doc, _) = single_image_document(10, 10, gradient(10, 10, 0));
+    // An encryption handler that cannot be opened without knowing it.
+    let encrypt_id = doc.add_object(dictionary! {
+        "Filter" => "Unknown",
+        "V" => 5,
+        "R" => 6,
     });
+    doc.trailer.set("Encrypt", encrypt_id);
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+
+    let result = crate::clean_pdf_report(&data, &Default::default());
+    assert!(matches!(result, Err(CleanError::Encrypted)));
+    assert!(matches!(
+        crate::detect_pdf(&data, &Default::default()),
+        Err(CleanError::Encrypted)
+    ));
+    // The shorthand entry points report it too, instead of panicking.
+    assert!(matches!(crate::clean_pdf(data, false), Err(CleanError::Encrypted)));
+    assert!(matches!(crate::clean_pdf(b"not a pdf".to_vec(), false), Err(CleanError::InvalidPdf(_))));
+}
+
+/// Cleans a synthetic document, returning the report and the cleaned document.
+fn clean_fixture(doc: lopdf::Document) -> (crate::CleanReport, lopdf::Document) {
+    let mut data = Vec::new();
+    let mut doc = doc;
+    doc.save_to(&mut data).unwrap();
+    let (cleaned, report) = crate::clean_pdf_report(&data, &Default::default()).unwrap();
+    (report, lopdf::Document::load_mem(&cleaned).unwrap())
+}
+
+/// Asserts that no ad image is visible on any page of a cleaned document.
+fn assert_no_visible_ads(doc: &lopdf::Document) {
+    use crate::models::{geometry::media_box, signature::AdSignatures};
+
+    for (number, id) in doc.get_pages() {
+        let rect = media_box(doc, &id).unwrap();
+        let ads = fixtures::visible_ads(doc, &id, &rect, AdSignatures::builtin());
+        assert_eq!(ads, 0, "page {number} still shows {ads} ads");
+    }
 }
 
 #[test]
-fn test_studocu_pdf() {
-    run_test_for_config(&TestConfig {
-        input_path: "example_docs/studocu-example.pdf",
-        output_filename: "studocu_clean.pdf",
+fn test_synthetic_wuolah() {
+    use crate::models::plan::PageAction;
+    use fixtures::{page_text, wuolah_document, WuolahPage::*};
+
+    let doc = wuolah_document(&[FullPageAd, Content, Content, FullPageAd, Content]);
+    let (report, cleaned) = clean_fixture(doc);
+    assert_eq!(report.method, "Wuolah");
+    let deleted: Vec<u32> = report
+        .pages
+        .iter()
+        .filter(|plan| plan.action == PageAction::Delete)
+        .map(|plan| plan.number)
+        .collect();
+    assert_eq!(deleted, vec![1, 4]);
+    for plan in report.pages.iter().filter(|plan| plan.number != 1 && plan.number != 4) {
+        // The banners drawn before and after the shared streams are dropped.
+        let PageAction::ReplaceContents { contents, .. } = &plan.action else {
+            panic!("page {} is not rewritten: {:?}", plan.number, plan.action);
+        };
+        assert_eq!(contents.len(), 8);
+    }
+
+    assert_eq!(cleaned.get_pages().len(), 3);
+    for (page, original) in [(1, 2), (2, 3), (3, 5)] {
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+    }
+    assert_no_visible_ads(&cleaned);
+}
+
+#[test]
+fn test_synthetic_wuolah_inserted_ads() {
+    use crate::models::plan::PageAction;
+    use fixtures::{page_text, wuolah_document, WuolahPage::*};
+
+    let doc = wuolah_document(&[FullPageAd, Content, WrappedAd, Simple, Content, FullPageAd]);
+    let (report, cleaned) = clean_fixture(doc);
+    assert_eq!(report.method, "Wuolah");
+    let deleted: Vec<u32> = report
+        .pages
+        .iter()
+        .filter(|plan| plan.action == PageAction::Delete)
+        .map(|plan| plan.number)
+        .collect();
+    // The ad laid out like the original pages is deleted, and the simple original page is not.
+    assert_eq!(deleted, vec![1, 3, 6]);
+    assert_eq!(report.pages[3].action, PageAction::Keep);
+
+    assert_eq!(cleaned.get_pages().len(), 3);
+    for (page, original) in [(1, 2), (2, 4), (3, 5)] {
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+    }
+    assert_no_visible_ads(&cleaned);
+}
+
+#[test]
+fn test_fallback_to_naive_when_pages_are_left_blank() {
+    use crate::clean::MethodAttempt;
+    use fixtures::{page_text, wuolah_document, WuolahPage::*};
+
+    let (report, _) = clean_fixture(wuolah_document(&[FullPageAd, Content, Content]));
+    assert_eq!(
+        report.attempts,
+        [MethodAttempt {
+            method: "Wuolah",
+            rejection: None
+        }]
+    );
+
+    // Move the text of the second page to the banner stream the Wuolah method drops.
+    let mut doc = wuolah_document(&[FullPageAd, Content, Content, Content]);
+    let page = doc.get_pages()[&2];
+    let contents = doc.get_page_contents(page);
+    let text = doc.get_object(contents[5]).unwrap().as_stream().unwrap().content.clone();
+    for (id, content) in [(contents[0], text), (contents[5], b"q Q".to_vec())] {
+        let stream = doc.get_object_mut(id).unwrap().as_stream_mut().unwrap();
+        stream.set_plain_content(content);
+    }
+
+    let (report, cleaned) = clean_fixture(doc);
+    assert_eq!(report.method, "Naive");
+    assert_eq!(report.attempts.len(), 2);
+    assert_eq!(report.attempts[0].method, "Wuolah");
+    let rejection = report.attempts[0].rejection.as_deref().unwrap();
+    assert!(rejection.contains("page 2"), "{rejection}");
+    assert_eq!(report.attempts[1].rejection, None);
+    let text = cleaned.extract_text(&[1]).unwrap();
+    assert!(text.contains(&page_text(2)), "{text:?}");
+}
+
+#[test]
+fn test_synthetic_studocu() {
+    use fixtures::{page_text, studocu_document, StuDocuPage::*};
+
+    let (report, cleaned) = clean_fixture(studocu_document(&[Cover, Content, Content]));
+    assert_eq!(report.method, "StuDocu");
+    assert_eq!(cleaned.get_pages().len(), 2);
+    for (page, original) in [(1, 2), (2, 3)] {
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+        assert!(!text.contains("Descargado por"), "page {page}: {text:?}");
+    }
+}
+
+#[test]
+fn test_synthetic_studocu_with_mixed_pages() {
+    use crate::models::plan::PageAction;
+    use fixtures::{page_text, studocu_document, StuDocuPage::*};
+
+    // A page added by the uploader, and a cover that is not the first page.
+    let doc = studocu_document(&[Content, Unstamped, Cover, Content]);
+    let (report, cleaned) = clean_fixture(doc);
+    assert_eq!(report.method, "StuDocu");
+    let actions: Vec<&PageAction> = report.pages.iter().map(|x| &x.action).collect();
+    assert!(matches!(actions[0], PageAction::ReplaceContents { contents, .. } if contents.len() == 1));
+    assert_eq!(actions[1], &PageAction::Keep);
+    assert_eq!(actions[2], &PageAction::Delete);
+
+    assert_eq!(cleaned.get_pages().len(), 3);
+    for (page, original) in [(1, 1), (2, 2), (3, 4)] {
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+        assert!(!text.contains("Descargado por"), "page {page}: {text:?}");
+    }
+
+    // Documents with mostly unstamped pages are not StuDocu downloads.
+    let (report, _) = clean_fixture(studocu_document(&[Content, Unstamped, Unstamped]));
+    assert_eq!(report.method, "Naive");
+}
+
+#[test]
+fn test_studocu_banners_are_removed() {
+    use crate::metadata::page_text_lines;
+    use fixtures::{page_text, studocu_document, StuDocuPage::*};
+
+    let banners = ["lOMoARcPSD", "Descar", "Distribution prohibited"];
+    let (report, cleaned) = clean_fixture(studocu_document(&[Cover, Inline, Content, Inline]));
+    assert_eq!(report.method, "StuDocu");
+    assert_eq!(report.pages[1].banners.len(), 1);
+    assert_eq!(cleaned.get_pages().len(), 3);
+    for (page, original) in [(1, 2), (2, 3), (3, 4)] {
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+        for banner in banners {
+            assert!(!text.contains(banner), "page {page}: {text:?}");
+        }
+    }
+
+    let data = std::fs::read("example_docs/studocu-example.pdf").expect("Failed to read PDF");
+    let (cleaned, _) = crate::clean_pdf_report(&data, &Default::default()).unwrap();
+    let cleaned = lopdf::Document::load_mem(&cleaned).unwrap();
+    for (number, id) in cleaned.get_pages() {
+        let text = page_text_lines(&cleaned, id).unwrap().concat();
+        for banner in banners {
+            assert!(!text.contains(banner), "page {number}: {text:?}");
+        }
+    }
+}
+
+#[test]
+fn test_studocu_quotes_are_kept() {
+    use crate::models::studocu::strip_banners;
+    use fixtures::{page_text, studocu_document, Fixture, StuDocuPage::*, STUDOCU_QUOTE};
+
+    // The original pages of a StuDocu document may say what the banners say.
+    let (report, cleaned) = clean_fixture(studocu_document(&[Cover, Quoting, Content]));
+    assert_eq!(report.method, "StuDocu");
+    assert!(report.pages[1].banners.is_empty());
+    let text = cleaned.extract_text(&[1]).unwrap();
+    assert!(text.contains(&page_text(2)), "{text:?}");
+    assert_eq!(text.matches(STUDOCU_QUOTE).count(), 2, "{text:?}");
+    assert!(!text.contains("lOMoARcPSD"), "{text:?}");
+
+    // And so may any other document.
+    let mut fixture = Fixture::new();
+    let mut contents = Vec::new();
+    for _ in 0..2 {
+        let content = fixture.text(STUDOCU_QUOTE);
+        fixture.page(vec![content], &[], vec![]);
+        contents.push(content);
+    }
+    let doc = fixture.build();
+    let (report, _) = clean_fixture(doc.clone());
+    assert_ne!(report.method, "StuDocu");
+    assert!(report.pages.iter().all(|x| x.banners.is_empty()));
+    for id in contents {
+        let content = &doc.get_object(id).unwrap().as_stream().unwrap().content;
+        assert_eq!(strip_banners(content), None);
+    }
+}
+
+#[test]
+fn test_studocu_needs_the_identifier() {
+    use crate::models::studocu::studocu_layout;
+    use fixtures::{page_text, Fixture};
+
+    // The download details alone, as a document quoting them would show, are not a stamp.
+    let mut fixture = Fixture::new();
+    let wrapper = fixture.stream(" q ");
+    for i in 1..=2 {
+        let content = fixture.text(&page_text(i));
+        let quote = fixture.stream(" Q BT /F1 8 Tf 50 2 Td (Downloaded by Anonimo) Tj ET");
+        fixture.page(vec![wrapper, content, quote], &[], vec![]);
+    }
+    let (report, _) = clean_fixture(fixture.build());
+    assert_ne!(report.method, "StuDocu");
+
+    // A stream that restores the graphics state without being a stamp keeps the stream that
+    // saves it.
+    let mut fixture = Fixture::new();
+    let wrapper = fixture.stream(" q ");
+    let content = fixture.text(&page_text(1));
+    let more = fixture.stream(" Q BT /F1 10 Tf 50 700 Td (Mas apuntes) Tj ET");
+    let stamp = fixture.stream("BT /F1 1 Tf 10 840 Td (lOMoARcPSD|123) Tj ET");
+    let page = fixture.page(vec![wrapper, content, more, stamp], &[], vec![]);
+    let doc = fixture.build();
+    let contents = vec![wrapper, content, more, stamp];
+    let layout = studocu_layout(&doc, &page, &contents, &Default::default()).unwrap();
+    assert_eq!(layout.added, [stamp]);
+    assert_eq!(layout.content, [wrapper, content, more]);
+}
+
+#[test]
+fn test_synthetic_studocu_overlays() {
+    use crate::models::{geometry::image_placements, plan::PageAction};
+    use fixtures::{page_text, studocu_document, StuDocuPage::*, STUDOCU_OVERLAY};
+
+    let (report, cleaned) = clean_fixture(studocu_document(&[Cover, Overlay, Preview, Content]));
+    assert_eq!(report.method, "StuDocu");
+    let incomplete: Vec<u32> = report
+        .pages
+        .iter()
+        .filter(|x| x.incomplete)
+        .map(|x| x.number)
+        .collect();
+    assert_eq!(incomplete, [3]);
+    assert!(matches!(&report.pages[1].action, PageAction::ReplaceContents { contents, .. } if contents.len() == 1));
+
+    // The overlay and the blurred preview are removed from the page that has its content.
+    assert_eq!(cleaned.get_pages().len(), 3);
+    let pages = cleaned.get_pages();
+    let text = cleaned.extract_text(&[1]).unwrap();
+    assert!(text.contains(&page_text(2)), "{text:?}");
+    assert!(!text.contains(STUDOCU_OVERLAY), "{text:?}");
+    assert!(image_placements(&cleaned, &pages[&1], &Default::default()).unwrap().is_empty());
+
+    // The preview is all there is of the incomplete page, so it is kept.
+    let text = cleaned.extract_text(&[2]).unwrap();
+    assert!(text.contains(STUDOCU_OVERLAY), "{text:?}");
+    assert!(!text.contains("Descargado por"), "{text:?}");
+    assert_eq!(image_placements(&cleaned, &pages[&2], &Default::default()).unwrap().len(), 1);
+}
+
+#[test]
+fn test_synthetic_studocu_overlay_text_needs_an_overlay() {
+    use fixtures::{page_text, studocu_document, StuDocuPage::*, STUDOCU_OVERLAY, STUDOCU_SLIDE_TITLE};
+
+    let (report, cleaned) = clean_fixture(studocu_document(&[Cover, Slide, Veiled]));
+    assert_eq!(report.method, "StuDocu");
+    assert!(report.pages.iter().all(|x| !x.incomplete));
+    assert_eq!(cleaned.get_pages().len(), 2);
+
+    // A title that says what the overlays say is kept, as nothing is drawn over the page.
+    let text = cleaned.extract_text(&[1]).unwrap();
+    assert!(text.contains(STUDOCU_SLIDE_TITLE), "{text:?}");
+    assert!(text.contains(&page_text(2)), "{text:?}");
+
+    // An overlay drawn on a veil over the whole page is removed, even without a preview.
+    let text = cleaned.extract_text(&[2]).unwrap();
+    assert!(text.contains(&page_text(3)), "{text:?}");
+    assert!(!text.contains(STUDOCU_OVERLAY), "{text:?}");
+}
+#[test]
+fn test_synthetic_naive_banners_and_full_page_ads() {
+    use crate::models::{
+        page_type::{PageType, FULL_PAGE_DIMS, HORIZONTAL_BANNER_DIMS, VERTICAL_BANNER_DIMS},
+        plan::PageAction,
+    };
+    use fixtures::{naive_document, NaivePage};
+
+    // Every banner size is paired with one of the other orientation.
+    let banners = HORIZONTAL_BANNER_DIMS.len().max(VERTICAL_BANNER_DIMS.len());
+    let mut pages: Vec<NaivePage> = (0..banners)
+        .map(|i| {
+            NaivePage::Banners(
+                HORIZONTAL_BANNER_DIMS[i % HORIZONTAL_BANNER_DIMS.len()],
+                VERTICAL_BANNER_DIMS[i % VERTICAL_BANNER_DIMS.len()],
+            )
+        })
+        .collect();
+    pages.extend(FULL_PAGE_DIMS.iter().map(|&dims| NaivePage::FullPageAd(dims)));
+
+    let (report, cleaned) = clean_fixture(naive_document(&pages));
+    assert_eq!(report.method, "Naive");
+    for (plan, page) in report.pages.iter().zip(&pages) {
+        match page {
+            NaivePage::Banners(..) => {
+                assert_eq!(plan.page_type, PageType::BannerAds, "{page:?}");
+                assert!(matches!(plan.action, PageAction::Rescale { .. }), "{page:?}");
+            }
+            _ => {
+                assert_eq!(plan.page_type, PageType::FullPageAds, "{page:?}");
+                assert_eq!(plan.action, PageAction::Delete, "{page:?}");
+            }
+        }
+    }
+    assert_eq!(cleaned.get_pages().len(), banners);
+    assert_no_visible_ads(&cleaned);
+}
+
+#[test]
+fn test_synthetic_naive_footers_and_logos() {
+    use crate::models::{
+        page_type::{PageType, LOGO_DIMS},
+        plan::PageAction,
+    };
+    use fixtures::{naive_document, NaivePage};
+
+    let mut pages = vec![NaivePage::Footer, NaivePage::Plain];
+    pages.extend(LOGO_DIMS.iter().map(|&dims| NaivePage::BannersWithLogo(dims)));
+    let doc = naive_document(&pages);
+    let (report, cleaned) = clean_fixture(doc);
+
+    assert_eq!(report.pages[0].page_type, PageType::Watermark);
+    assert!(matches!(report.pages[0].action, PageAction::Crop(_)));
+    assert_eq!(report.pages[1].page_type, PageType::Idk);
+    assert_eq!(report.pages[1].action, PageAction::Delete);
+    for (plan, page) in report.pages.iter().zip(&pages).skip(2) {
+        assert_eq!(plan.page_type, PageType::BannerAds, "{page:?}");
+        assert_eq!(plan.logos.len(), 1, "{page:?}");
+    }
+
+    assert_eq!(cleaned.get_pages().len(), pages.len() - 1);
+    assert_no_visible_ads(&cleaned);
+}
+
+#[test]
+fn test_delete_pages_updates_the_page_tree() {
+    use crate::models::analysis::delete_pages;
+    use fixtures::{naive_document, NaivePage};
+    use lopdf::{dictionary, Object};
+
+    let mut doc = naive_document(&[NaivePage::Plain; 5]);
+    let pages = doc.get_pages();
+    // An outline item pointing at a page that is deleted.
+    let outline = doc.add_object(dictionary! {
+        "Title" => Object::string_literal("Page 2"),
+        "Dest" => vec![pages[&2].into(), "Fit".into()],
     });
-}
\ No newline at end of file
+    doc.catalog_mut().unwrap().set("Outlines", outline);
+
+    delete_pages(&mut doc, &[pages[&2], pages[&4]]);
+
+    let remaining: Vec<_> = doc.get_pages().into_values().collect();
+    assert_eq!(remaining, vec![pages[&1], pages[&3], pages[&5]]);
+    let root = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+    let count = doc.get_dictionary(root).unwrap().get(b"Count").unwrap();
+    assert_eq!(count.as_i64().unwrap(), 3);
+    let dest = doc.get_dictionary(outline).unwrap().get(b"Dest").unwrap();
+    assert_eq!(dest.as_array().unwrap(), &vec![Object::from("Fit")]);
+}
+
+#[test]
+fn test_large_documents_clean_in_linear_time() {
+    use crate::clean::{clean_pdf_report, CleanOptions};
+    use crate::models::analysis::{ANALYSED_PAGES, DECODED_CONTENTS};
+    use fixtures::{naive_document, wuolah_document, NaivePage, WuolahPage};
+
+    // Counts the pages analysed and the contents decoded while cleaning `doc`.
+    let clean = |mut doc: lopdf::Document, method: &str, kept: usize| -> (usize, usize) {
+        let mut data = Vec::new();
+        doc.save_to(&mut data).unwrap();
+        let options = CleanOptions {
+            verify: true,
+            ..Default::default()
+        };
+        ANALYSED_PAGES.with(|x| x.set(0));
+        DECODED_CONTENTS.with(|x| x.set(0));
+        let (cleaned, report) = clean_pdf_report(&data, &options).unwrap();
+        assert_eq!(report.method, method);
+        assert_eq!(lopdf::Document::load_mem(&cleaned).unwrap().get_pages().len(), kept);
+        (ANALYSED_PAGES.with(|x| x.get()), DECODED_CONTENTS.with(|x| x.get()))
+    };
+
+    // Every page is analysed once and decoded at most once, however large the document is.
+    for pages in [200, 800] {
+        let layout: Vec<WuolahPage> = (0..pages)
+            .map(|i| match i % 4 {
+                3 => WuolahPage::FullPageAd,
+                _ => WuolahPage::Content,
+            })
+            .collect();
+        let (analysed, decoded) = clean(wuolah_document(&layout), "Wuolah", pages - pages / 4);
+        assert_eq!(analysed, pages, "{pages} Wuolah pages were analysed {analysed} times");
+        assert!(decoded <= pages, "{pages} Wuolah pages were decoded {decoded} times");
+
+        // The naive method rescales the pages with banners, which needs their content. Its
+        // images make it slower, so the document is smaller.
+        let pages = pages / 4;
+        let layout: Vec<NaivePage> = (0..pages)
+            .map(|_| NaivePage::Banners((247, 1414), (1753, 170)))
+            .collect();
+        let (analysed, decoded) = clean(naive_document(&layout), "Naive", pages);
+        assert_eq!(analysed, pages, "{pages} Naive pages were analysed {analysed} times");
+        assert!(decoded > 0 && decoded <= pages, "{pages} Naive pages were decoded {decoded} times");
+    }
+}
+
+#[test]
+fn test_validation_accepts_clean_outputs() {
+    use fixtures::{naive_document, studocu_document, wuolah_document, NaivePage, StuDocuPage, WuolahPage};
+
+    let documents = [
+        wuolah_document(&[WuolahPage::Content, WuolahPage::FullPageAd, WuolahPage::Content]),
+        studocu_document(&[StuDocuPage::Cover, StuDocuPage::Content, StuDocuPage::Overlay]),
+        naive_document(&[NaivePage::BannersWithLogo((24, 130)), NaivePage::Footer]),
+    ];
+    for doc in documents {
+        let (report, _) = clean_fixture(doc);
+        assert_eq!(report.diagnostics, Some(vec![]), "{} output", report.method);
+    }
+
+    let data = fs::read("example_docs/wuolah-170924-example.pdf").unwrap();
+    let options = crate::CleanOptions {
+        preview: true,
+        ..Default::default()
+    };
+    let (_, report) = crate::clean_pdf_report(&data, &options).unwrap();
+    assert_eq!(report.diagnostics, Some(vec![]));
+}
+
+#[test]
+fn test_validation_reports_broken_outputs() {
+    use crate::validate::{validate_pdf, Diagnostic};
+    use fixtures::{naive_document, NaivePage};
+    use lopdf::{Document, Object};
+
+    let (report, mut doc) = clean_fixture(naive_document(&[NaivePage::Footer; 3]));
+    let pages: Vec<_> = doc.get_pages().into_values().collect();
+    let missing = (doc.max_id + 10, 0);
+
+    // Naive scale math gone wrong, a dangling content stream and a font that no longer exists.
+    let first = doc.get_dictionary_mut(pages[0]).unwrap();
+    first.set("MediaBox", vec![0.into(), 0.into(), (-595).into(), 842.into()]);
+    first.set("CropBox", vec![0.into()]);
+    let second = doc.get_dictionary_mut(pages[1]).unwrap();
+    second.set("Contents", vec![Object::Reference(missing)]);
+    let third = doc.get_dictionary_mut(pages[2]).unwrap();
+    let resources = third.get_mut(b"Resources").unwrap().as_dict_mut().unwrap();
+    resources.set("Font", lopdf::dictionary! { "F1" => missing });
+
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    let diagnostics = validate_pdf(&data, &report);
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::DegenerateBox {
+                page: 1,
+                name: "MediaBox".to_string(),
+                rect: [0.0, 0.0, -595.0, 842.0],
+            },
+            Diagnostic::MalformedBox {
+                page: 1,
+                name: "CropBox".to_string(),
+            },
+            Diagnostic::UnresolvedContents {
+                page: 2,
+                object: missing,
+            },
+            Diagnostic::UnresolvedResource {
+                page: 3,
+                name: "/Font/F1".to_string(),
+                object: missing,
+            },
+        ]
+    );
+    assert_eq!(diagnostics[2].page(), Some(2));
+
+    // A report that kept a page the document does not have.
+    let mut report = report;
+    report.pages.push(report.pages[0].clone());
+    let mut doc = Document::load_mem(&data).unwrap();
+    doc.get_dictionary_mut(pages[2]).unwrap().remove(b"Resources");
+    let mut data = Vec::new();
+    doc.save_to(&mut data).unwrap();
+    let diagnostics = validate_pdf(&data, &report);
+    assert!(diagnostics.contains(&Diagnostic::PageCountMismatch { expected: 4, found: 3 }));
+    assert!(diagnostics.contains(&Diagnostic::MissingResources { page: 3 }));
+
+    assert!(matches!(
+        validate_pdf(b"not a pdf", &report)[..],
+        [Diagnostic::Unreadable(_)]
+    ));
+
+    // The naive method deletes every page it does not recognise.
+    let (report, _) = clean_fixture(naive_document(&[NaivePage::Plain; 2]));
+    assert_eq!(report.diagnostics, Some(vec![Diagnostic::NoPages]));
+}
+
+#[test]
+fn test_synthetic_docsity() {
+    use crate::models::{
+        plan::PageAction,
+        signature::{AdSignature, AdSignatures},
+    };
+    use fixtures::{docsity_document, page_text, DocsityPage::*, DOCSITY_FOOTER_DIMS};
+
+    let pages = [Cover, Content, Promo, Unlinked, Scanned, Plain, Content];
+    let (report, cleaned) = clean_fixture(docsity_document(&pages));
+    assert_eq!(report.method, "Docsity");
+    assert_eq!(report.method_code, 3);
+    let footer = [0.0, 40.0, 595.0, 842.0];
+    let expected = [
+        PageAction::Delete,
+        PageAction::Crop(footer),
+        PageAction::Delete,
+        PageAction::Crop(footer),
+        PageAction::Crop(footer),
+        PageAction::Keep,
+        PageAction::Crop(footer),
+    ];
+    for ((plan, action), page) in report.pages.iter().zip(&expected).zip(&pages) {
+        assert_eq!(&plan.action, action, "{page:?}");
+        let hidden = if *action == PageAction::Keep || *action == PageAction::Delete { 0 } else { 1 };
+        assert_eq!(plan.logos.len(), hidden, "{page:?}");
+    }
+
+    assert_eq!(cleaned.get_pages().len(), 5);
+    let footers = AdSignatures {
+        logos: vec![AdSignature::from_dims(DOCSITY_FOOTER_DIMS, 0)],
+        ..Default::default()
+    };
+    for (page, original) in [(1, 2), (2, 4), (3, 5), (4, 6), (5, 7)] {
+        let id = cleaned.get_pages()[&page];
+        let text = cleaned.extract_text(&[page]).unwrap();
+        assert!(text.contains(&page_text(original)), "page {page}: {text:?}");
+        assert_eq!(fixtures::visible_ads(&cleaned, &id, &fixtures::A4, &footers), 0, "page {page}");
+        let annots = cleaned.get_dictionary(id).unwrap().get(b"Annots").unwrap();
+        assert!(annots.as_array().unwrap().is_empty(), "page {page}");
+    }
+}
+
+#[test]
+fn test_docsity_needs_most_pages_linked() {
+    use fixtures::{docsity_document, DocsityPage::*};
+
+    // A single linked page among the uploader's own is not enough to take it for Docsity.
+    let (report, _) = clean_fixture(docsity_document(&[Content, Plain, Plain]));
+    assert_ne!(report.method, "Docsity");
+
+    // The pages the uploader added are kept as they are.
+    let (report, cleaned) = clean_fixture(docsity_document(&[Cover, Plain]));
+    assert_eq!(report.method, "Docsity");
+    assert_eq!(report.pages[1].action, crate::models::plan::PageAction::Keep);
+    assert_eq!(cleaned.get_pages().len(), 1);
+}
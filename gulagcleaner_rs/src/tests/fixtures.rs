@@ -0,0 +1,524 @@
+//! Synthetic documents that mimic the layouts of each source, built with lopdf.
+//!
+//! The real downloads are copyrighted and cannot be regenerated when a layout changes, so the
+//! detection and cleaning branches are tested against these instead. Every page draws its text
+//! with a standard font, so `extract_text` can tell whether the content survived cleaning.
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::models::{
+    geometry::{image_placements, Rect},
+    page_type::{FULL_PAGE_DIMS, HORIZONTAL_BANNER_DIMS, VERTICAL_BANNER_DIMS},
+    resources::PageImage,
+    signature::AdSignatures,
+};
+
+/// The size of an A4 page, in points.
+pub const A4: Rect = [0.0, 0.0, 595.0, 842.0];
+
+/// The URL of the footer links that Wuolah adds to its pages.
+pub const FOOTER_URL: &str = "https://track.wlh.es/click?adU=2&id=123";
+
+/// The URL of the links over the full page ads of Wuolah.
+pub const FULL_PAGE_AD_URL: &str = "https://track.wlh.es/click?adU=1&id=456";
+
+/// The text drawn on the content of a page.
+pub fn page_text(number: usize) -> String {
+    format!("Apuntes de la pagina {number}. Lorem ipsum dolor sit amet, consectetur adipiscing.")
+}
+
+/// A document under construction, whose pages share a font and are added in order.
+pub struct Fixture {
+    pub doc: Document,
+    pages_id: ObjectId,
+    font_id: ObjectId,
+    kids: Vec<Object>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        Fixture {
+            doc,
+            pages_id,
+            font_id,
+            kids: Vec::new(),
+        }
+    }
+
+    /// Adds a grayscale image of the given `(height, width)`.
+    pub fn image(&mut self, (height, width): (i64, i64)) -> ObjectId {
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width,
+                "Height" => height,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![0x80; (height * width) as usize],
+        );
+        // Full page ads are large, but compress to almost nothing.
+        let _ = stream.compress();
+        self.doc.add_object(stream)
+    }
+
+    /// Adds a content stream.
+    pub fn stream(&mut self, content: &str) -> ObjectId {
+        self.doc
+            .add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()))
+    }
+
+    /// Adds a content stream that draws a line of text near the top of the page.
+    pub fn text(&mut self, text: &str) -> ObjectId {
+        self.stream(&format!("BT /F1 10 Tf 50 780 Td ({text}) Tj ET"))
+    }
+
+    /// Adds a page drawing `contents`, with `images` available under the names `Im0`, `Im1`...
+    pub fn page(
+        &mut self,
+        contents: Vec<ObjectId>,
+        images: &[ObjectId],
+        annots: Vec<Object>,
+    ) -> ObjectId {
+        let xobjects: Dictionary = images
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (format!("Im{i}").into_bytes(), Object::Reference(*id)))
+            .collect();
+        let page = self.doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "MediaBox" => A4.iter().map(|x| Object::Real(*x)).collect::<Vec<_>>(),
+            "Contents" => contents.into_iter().map(Object::Reference).collect::<Vec<_>>(),
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "F1" => self.font_id },
+                "XObject" => xobjects,
+            },
+            "Annots" => annots,
+        });
+        self.kids.push(page.into());
+        page
+    }
+
+    /// Adds a link annotation along the bottom of the page, like the Wuolah footers.
+    pub fn footer_link(&mut self, url: &str) -> Object {
+        self.link(url, [0.0, 0.0, 595.0, 40.0])
+    }
+
+    /// Adds a link annotation over a rectangle of the page.
+    pub fn link(&mut self, url: &str, rect: Rect) -> Object {
+        self.doc
+            .add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => rect.iter().map(|x| Object::Real(*x)).collect::<Vec<_>>(),
+                "A" => dictionary! {
+                    "S" => "URI",
+                    "URI" => Object::string_literal(url),
+                },
+            })
+            .into()
+    }
+
+    /// Finishes the page tree and returns the document.
+    pub fn build(mut self) -> Document {
+        let count = self.kids.len() as i64;
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => self.kids,
+                "Count" => count,
+            }),
+        );
+        let catalog_id = self.doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        });
+        self.doc.trailer.set("Root", catalog_id);
+        self.doc
+    }
+}
+
+/// The operators that draw an image over a rectangle.
+fn draw(name: &str, [x0, y0, x1, y1]: Rect) -> String {
+    format!("q {} 0 0 {} {x0} {y0} cm /{name} Do Q\n", x1 - x0, y1 - y0)
+}
+
+/// A page of a Wuolah document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WuolahPage {
+    /// A page of the original document, with banners drawn by streams of its own around the
+    /// streams it shares with the other pages.
+    Content,
+    /// An ad inserted between the pages, drawn by a single stream.
+    FullPageAd,
+    /// An ad laid out like the original pages, with a tracking link over the whole page.
+    WrappedAd,
+    /// A page of the original document drawn by a single stream, without banners.
+    Simple,
+}
+
+/// Builds a Wuolah document.
+///
+/// Content pages draw the original page between streams shared by every page, and banners
+/// before and after them. The cleaner keeps the streams between the shared ones, which hold
+/// the text, and drops the banners.
+pub fn wuolah_document(pages: &[WuolahPage]) -> Document {
+    let mut fixture = Fixture::new();
+    let shared: Vec<ObjectId> = (0..4).map(|_| fixture.stream("q Q")).collect();
+    let banner = fixture.image(HORIZONTAL_BANNER_DIMS[0]);
+    let side_banner = fixture.image(VERTICAL_BANNER_DIMS[0]);
+    let full_page = fixture.image(FULL_PAGE_DIMS[0]);
+
+    for (i, page) in pages.iter().enumerate() {
+        match page {
+            WuolahPage::Content => {
+                let top = fixture.stream(&draw("Im0", [0.0, 782.0, 595.0, 842.0]));
+                let bottom = fixture.stream(&draw("Im1", [535.0, 0.0, 595.0, 782.0]));
+                let keep: Vec<ObjectId> = (0..4).map(|_| fixture.stream("q Q")).collect();
+                let text = fixture.text(&page_text(i + 1));
+                let contents = vec![
+                    top, shared[0], keep[0], keep[1], shared[1], text, shared[2], keep[2], keep[3],
+                    shared[3], bottom,
+                ];
+                fixture.page(contents, &[banner, side_banner], vec![]);
+            }
+            WuolahPage::FullPageAd => {
+                let ad = fixture.stream(&draw("Im0", A4));
+                fixture.page(vec![ad], &[full_page], vec![]);
+            }
+            WuolahPage::WrappedAd => {
+                let keep: Vec<ObjectId> = (0..6).map(|_| fixture.stream("q Q")).collect();
+                let ad = fixture.stream(&draw("Im0", A4));
+                let contents = vec![
+                    keep[0], shared[0], keep[1], keep[2], shared[1], ad, shared[2], keep[3],
+                    keep[4], shared[3], keep[5],
+                ];
+                let link = fixture.link(FULL_PAGE_AD_URL, [5.0, 5.0, 590.0, 837.0]);
+                fixture.page(contents, &[full_page], vec![link]);
+            }
+            WuolahPage::Simple => {
+                let text = fixture.text(&page_text(i + 1));
+                fixture.page(vec![text], &[], vec![]);
+            }
+        }
+    }
+    fixture.build()
+}
+
+/// A page of a StuDocu document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StuDocuPage {
+    /// The StuDocu cover, whose stamp draws the cover again.
+    Cover,
+    /// A page of the original document, with the StuDocu stamp over it.
+    Content,
+    /// A page added by the uploader after the download, with a single stream and no stamp.
+    Unstamped,
+    /// A page of the original document under a blurred preview and a premium overlay.
+    Overlay,
+    /// A blurred preview and a premium overlay, without the original content underneath.
+    Preview,
+    /// A page of the original document under a premium overlay drawn on a white veil over the
+    /// whole page, without a blurred preview.
+    Veiled,
+    /// A page of the original document whose title speaks of unlocking, like the overlays do,
+    /// in its own stream.
+    Slide,
+    /// A page of the original document whose only stream also draws the StuDocu banners, the
+    /// "Downloaded by" line split by kerning.
+    Inline,
+    /// A page of the original document that quotes the StuDocu banners in its own text, once in
+    /// its body and once in a footnote set in a small font.
+    Quoting,
+}
+
+/// The text StuDocu draws at the top and the bottom of the pages it serves.
+const STUDOCU_STAMP: &str =
+    "BT /F1 1 Tf 10 840 Td (lOMoARcPSD|123) Tj ET BT /F1 8 Tf 50 2 Td (Descargado por Anonimo) Tj ET";
+
+/// What the original pages may quote of the StuDocu banners.
+pub const STUDOCU_QUOTE: &str = "Downloaded by students, distribution prohibited";
+
+/// The text of the overlay StuDocu draws over the pages that need premium.
+pub const STUDOCU_OVERLAY: &str = "Desbloquea este documento con Premium";
+
+/// The title of a slide of the original document, which says what the overlays say.
+pub const STUDOCU_SLIDE_TITLE: &str = "Unlock the potential of your data";
+
+/// Builds a StuDocu document.
+///
+/// Stamped pages draw their own streams between a stream that saves the graphics state, shared
+/// by every page, and a stamp stream that restores it and draws the download details.
+pub fn studocu_document(pages: &[StuDocuPage]) -> Document {
+    let mut fixture = Fixture::new();
+    let wrapper = fixture.stream(" q ");
+    let blurred = fixture.image((84, 60));
+    for (i, page) in pages.iter().enumerate() {
+        match page {
+            StuDocuPage::Cover => {
+                let cover = "BT /F1 24 Tf 50 700 Td (Studocu) Tj ET";
+                let content = fixture.stream(cover);
+                let stamp = fixture.stream(&format!(" Q {cover} {STUDOCU_STAMP}"));
+                fixture.page(vec![wrapper, content, stamp], &[], vec![]);
+            }
+            StuDocuPage::Content => {
+                let content = fixture.text(&page_text(i + 1));
+                let stamp = fixture.stream(&format!(" Q {STUDOCU_STAMP}"));
+                fixture.page(vec![wrapper, content, stamp], &[], vec![]);
+            }
+            StuDocuPage::Unstamped => {
+                let content = fixture.text(&page_text(i + 1));
+                fixture.page(vec![content], &[], vec![]);
+            }
+            StuDocuPage::Inline => {
+                let content = fixture.stream(&format!(
+                    "BT /F1 10 Tf 50 780 Td ({}) Tj ET \
+                     BT /F1 1 Tf 10 840 Td (lOMoARcPSD|123) Tj ET \
+                     BT /F1 8 Tf 50 2 Td [(Descar) -20 (gado por Anonimo)] TJ ET \
+                     BT /F1 8 Tf 300 2 Td (Distribution prohibited) Tj ET",
+                    page_text(i + 1)
+                ));
+                fixture.page(vec![content], &[], vec![]);
+            }
+            StuDocuPage::Quoting => {
+                let content = fixture.stream(&format!(
+                    "BT /F1 10 Tf 50 780 Td ({}) Tj 0 -14 Td ({STUDOCU_QUOTE}) Tj ET \
+                     BT /F1 8 Tf 50 60 Td (1. Notes) Tj 0 -10 Td ({STUDOCU_QUOTE}) Tj \
+                     0 -10 Td (by the authors.) Tj ET",
+                    page_text(i + 1)
+                ));
+                let stamp = fixture.stream(&format!(" Q {STUDOCU_STAMP}"));
+                fixture.page(vec![wrapper, content, stamp], &[], vec![]);
+            }
+            StuDocuPage::Overlay | StuDocuPage::Preview => {
+                let mut contents = vec![wrapper];
+                if *page == StuDocuPage::Overlay {
+                    contents.push(fixture.text(&page_text(i + 1)));
+                }
+                contents.push(fixture.stream(&draw("Im0", A4)));
+                contents.push(fixture.stream(&format!(
+                    "BT /F1 16 Tf 150 420 Td ({STUDOCU_OVERLAY}) Tj ET"
+                )));
+                contents.push(fixture.stream(&format!(" Q {STUDOCU_STAMP}")));
+                fixture.page(contents, &[blurred], vec![]);
+            }
+            StuDocuPage::Veiled => {
+                let content = fixture.text(&page_text(i + 1));
+                let overlay = fixture.stream(&format!(
+                    "q 1 g 0 0 595 842 re f Q BT /F1 16 Tf 150 420 Td ({STUDOCU_OVERLAY}) Tj ET"
+                ));
+                let stamp = fixture.stream(&format!(" Q {STUDOCU_STAMP}"));
+                fixture.page(vec![wrapper, content, overlay, stamp], &[], vec![]);
+            }
+            StuDocuPage::Slide => {
+                let title = fixture.stream(&format!(
+                    "BT /F1 24 Tf 50 780 Td ({STUDOCU_SLIDE_TITLE}) Tj ET"
+                ));
+                let content = fixture.text(&page_text(i + 1));
+                let stamp = fixture.stream(&format!(" Q {STUDOCU_STAMP}"));
+                fixture.page(vec![wrapper, title, content, stamp], &[], vec![]);
+            }
+        }
+    }
+    fixture.build()
+}
+
+/// The URL of the links Docsity adds to the pages it serves.
+pub const DOCSITY_URL: &str = "https://www.docsity.com/es/?utm_source=download";
+
+/// The `(height, width)` of the footer banner Docsity draws along the bottom of its pages.
+pub const DOCSITY_FOOTER_DIMS: (i64, i64) = (68, 1240);
+
+/// A page of a Docsity document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocsityPage {
+    /// The branded cover, a full page image with a link over it.
+    Cover,
+    /// A page of the original document, with a linked footer banner along the bottom.
+    Content,
+    /// A page of the original document with the footer banner, whose link was removed.
+    Unlinked,
+    /// A promo page, with a link over most of it.
+    Promo,
+    /// A scanned page of the original document, an image over the whole page with the footer
+    /// banner on top of it.
+    Scanned,
+    /// A page added by the uploader after the download, without banners or links.
+    Plain,
+}
+
+/// Builds a Docsity document.
+///
+/// Every page served by Docsity draws the same footer banner image along the bottom, under a
+/// link to Docsity, and the cover and promo pages are linked to it as a whole.
+pub fn docsity_document(pages: &[DocsityPage]) -> Document {
+    const FOOTER: Rect = [0.0, 0.0, 595.0, 40.0];
+    let mut fixture = Fixture::new();
+    let footer = fixture.image(DOCSITY_FOOTER_DIMS);
+    let cover = fixture.image((1754, 1240));
+    let scan = fixture.image((1169, 826));
+    for (i, page) in pages.iter().enumerate() {
+        let text = page_text(i + 1);
+        match page {
+            DocsityPage::Cover => {
+                let content = fixture.stream(&draw("Im0", A4));
+                let link = fixture.link(DOCSITY_URL, A4);
+                fixture.page(vec![content], &[cover], vec![link]);
+            }
+            DocsityPage::Content | DocsityPage::Unlinked => {
+                let content = fixture.text(&text);
+                let banner = fixture.stream(&draw("Im0", FOOTER));
+                let links = match page {
+                    DocsityPage::Content => vec![fixture.link(DOCSITY_URL, FOOTER)],
+                    _ => vec![],
+                };
+                fixture.page(vec![content, banner], &[footer], links);
+            }
+            DocsityPage::Promo => {
+                let content = fixture.stream(
+                    "BT /F1 24 Tf 100 600 Td (Descubre miles de apuntes en Docsity) Tj ET",
+                );
+                let link = fixture.link(DOCSITY_URL, [50.0, 100.0, 545.0, 742.0]);
+                fixture.page(vec![content], &[], vec![link]);
+            }
+            DocsityPage::Scanned => {
+                let content = fixture.stream(&format!(
+                    "{}BT 3 Tr /F1 10 Tf 100 700 Td ({text}) Tj ET\n{}",
+                    draw("Im0", A4),
+                    draw("Im1", FOOTER)
+                ));
+                let link = fixture.link(DOCSITY_URL, FOOTER);
+                fixture.page(vec![content], &[scan, footer], vec![link]);
+            }
+            DocsityPage::Plain => {
+                let content = fixture.text(&text);
+                fixture.page(vec![content], &[], vec![]);
+            }
+        }
+    }
+    fixture.build()
+}
+
+/// A page of a document that is only recognised by the images and links on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NaivePage {
+    /// The original page with banners along the top and the side.
+    Banners((i64, i64), (i64, i64)),
+    /// The original page with banners, and a logo on top of it.
+    BannersWithLogo((i64, i64)),
+    /// A full page ad.
+    FullPageAd((i64, i64)),
+    /// The original page with a footer link to Wuolah.
+    Footer,
+    /// A page with nothing that identifies it.
+    Plain,
+}
+
+/// Builds a document with one content stream per page, which is cleaned with the naive method.
+pub fn naive_document(pages: &[NaivePage]) -> Document {
+    let mut fixture = Fixture::new();
+    for (i, page) in pages.iter().enumerate() {
+        let text = page_text(i + 1);
+        let text = format!("BT /F1 10 Tf 100 700 Td ({text}) Tj ET\n");
+        match *page {
+            NaivePage::Banners(horizontal, vertical) => {
+                let images = [fixture.image(horizontal), fixture.image(vertical)];
+                let content = fixture.stream(&format!(
+                    "{}{}{text}",
+                    draw("Im0", [0.0, 782.0, 595.0, 842.0]),
+                    draw("Im1", [0.0, 0.0, 60.0, 782.0]),
+                ));
+                fixture.page(vec![content], &images, vec![]);
+            }
+            NaivePage::BannersWithLogo(logo) => {
+                let images = [
+                    fixture.image(HORIZONTAL_BANNER_DIMS[0]),
+                    fixture.image(VERTICAL_BANNER_DIMS[0]),
+                    fixture.image(logo),
+                ];
+                let content = fixture.stream(&format!(
+                    "{}{}{}{text}",
+                    draw("Im0", [0.0, 782.0, 595.0, 842.0]),
+                    draw("Im1", [0.0, 0.0, 60.0, 782.0]),
+                    draw("Im2", [400.0, 720.0, 530.0, 744.0]),
+                ));
+                fixture.page(vec![content], &images, vec![]);
+            }
+            NaivePage::FullPageAd(dims) => {
+                let image = fixture.image(dims);
+                let content = fixture.stream(&draw("Im0", A4));
+                fixture.page(vec![content], &[image], vec![]);
+            }
+            NaivePage::Footer => {
+                let link = fixture.footer_link(FOOTER_URL);
+                let content = fixture.stream(&text);
+                fixture.page(vec![content], &[], vec![link]);
+            }
+            NaivePage::Plain => {
+                let content = fixture.stream(&text);
+                fixture.page(vec![content], &[], vec![]);
+            }
+        }
+    }
+    fixture.build()
+}
+
+/// Counts the images matching an ad signature that are drawn inside the visible area of a page.
+pub fn visible_ads(
+    doc: &Document,
+    page: &ObjectId,
+    visible: &Rect,
+    signatures: &AdSignatures,
+) -> usize {
+    let all_signatures = [
+        &signatures.horizontal_banners,
+        &signatures.vertical_banners,
+        &signatures.full_page,
+        &signatures.logos,
+    ];
+    image_placements(doc, page, &Default::default())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|placement| {
+            let overlap_x = placement.rect[2].min(visible[2]) - placement.rect[0].max(visible[0]);
+            let overlap_y = placement.rect[3].min(visible[3]) - placement.rect[1].max(visible[1]);
+            overlap_x > 1.0 && overlap_y > 1.0
+        })
+        .filter_map(|placement| {
+            let dict = &doc.get_object(placement.id).ok()?.as_stream().ok()?.dict;
+            let dim = |key: &[u8]| dict.get(key).and_then(Object::as_i64).unwrap_or(0);
+            Some(PageImage {
+                id: placement.id,
+                name: vec![],
+                height: dim(b"Height"),
+                width: dim(b"Width"),
+                forms: vec![],
+            })
+        })
+        // Logos are hidden by setting their height to zero.
+        .filter(|image| image.height > 0 && image.width > 0)
+        .filter(|image| {
+            all_signatures
+                .iter()
+                .flat_map(|x| x.iter())
+                .any(|signature| signature.matches(doc, image))
+        })
+        .count()
+}
@@ -0,0 +1,194 @@
+//! Regression tests that clean every sample document and compare the result with its manifest.
+//!
+//! Each `example_docs/<name>.pdf` has a manifest at `example_docs/golden/<name>.golden` listing
+//! the method, what is done to each original page, and the boxes and visible ad images of each
+//! page of the output. Run with `GULAGCLEANER_BLESS=1` to write the manifests from the current
+//! output after checking the changes are intended.
+
+use std::{env, fmt::Write, fs, path::Path};
+
+use lopdf::Document;
+
+use super::{create_output_directory, fixtures::visible_ads, OUT_PATH};
+use crate::{
+    clean::{clean_pdf_report, CleanOptions},
+    models::geometry::{media_box, Rect},
+};
+
+const FIXTURES_PATH: &str = "example_docs";
+const GOLDEN_PATH: &str = "example_docs/golden";
+
+/// Cleans a sample document and describes the result in the manifest format.
+fn render_manifest(name: &str, data: &[u8]) -> Result<String, String> {
+    let options = CleanOptions::default();
+    let (cleaned, report) =
+        clean_pdf_report(data, &options).map_err(|e| format!("cleaning failed: {e}"))?;
+    fs::write(format!("{OUT_PATH}/{name}_clean.pdf"), &cleaned)
+        .map_err(|e| format!("failed to store the output: {e}"))?;
+    let doc = Document::load_mem(&cleaned).map_err(|e| format!("the output is invalid: {e}"))?;
+    // Blessing must not be able to accept a broken output either.
+    if let Some(diagnostic) = report.diagnostics.iter().flatten().next() {
+        return Err(format!("the output is broken: {diagnostic}"));
+    }
+
+    let mut manifest = String::new();
+    let mut line = |text: String| {
+        manifest.push_str(&text);
+        manifest.push('\n');
+    };
+    line(format!("# Golden manifest for {name}.pdf"));
+    line(format!("method {}", report.method));
+    line(format!("output_pages {}", doc.get_pages().len()));
+    for plan in &report.pages {
+        line(format!(
+            "page {} {:?} {}{}",
+            plan.number,
+            plan.page_type,
+            plan.action.describe(),
+            if plan.incomplete { " incomplete" } else { "" }
+        ));
+    }
+    for (number, id) in doc.get_pages() {
+        let rect = media_box(&doc, &id).map_err(|e| format!("output page {number}: {e}"))?;
+        line(format!(
+            "output {number} box {} ads {}",
+            format_rect(&rect),
+            visible_ads(&doc, &id, &rect, &options.signatures)
+        ));
+    }
+    Ok(manifest)
+}
+
+fn format_rect(rect: &Rect) -> String {
+    rect.iter().fold(String::new(), |mut text, x| {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let _ = write!(text, "{x:.2}");
+        text
+    })
+}
+
+/// A line-based diff of two texts, showing the lines only in `expected` with `-` and the lines
+/// only in `actual` with `+`, with their line numbers.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Length of the longest common subsequence of the suffixes starting at each pair of lines.
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut text = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(text, "{:>4} - {}", i + 1, expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(text, "{:>4} + {}", j + 1, actual[j]);
+            j += 1;
+        }
+    }
+    text
+}
+
+#[test]
+fn test_golden_manifests() {
+    create_output_directory();
+    let bless = env::var_os("GULAGCLEANER_BLESS").is_some();
+    if bless {
+        fs::create_dir_all(GOLDEN_PATH).expect("Failed to create the golden directory");
+    }
+
+    let mut fixtures: Vec<String> = fs::read_dir(FIXTURES_PATH)
+        .expect("Failed to list the sample documents")
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            (path.extension()? == "pdf").then(|| path.file_stem()?.to_str().map(String::from))?
+        })
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "There are no sample documents");
+
+    let mut failures = Vec::new();
+    for name in &fixtures {
+        let data = fs::read(format!("{FIXTURES_PATH}/{name}.pdf")).expect("Failed to read PDF");
+        let actual = match render_manifest(name, &data) {
+            Ok(actual) => actual,
+            Err(e) => {
+                failures.push(format!("{name}: {e}"));
+                continue;
+            }
+        };
+
+        // Blessing must not be able to accept ads left in the output.
+        if let Some(page) = actual
+            .lines()
+            .find(|line| line.starts_with("output ") && !line.ends_with(" ads 0"))
+        {
+            failures.push(format!("{name}: ad images are still visible: {page}"));
+        }
+
+        let manifest_path = format!("{GOLDEN_PATH}/{name}.golden");
+        if bless {
+            fs::write(&manifest_path, &actual).expect("Failed to write the manifest");
+            continue;
+        }
+        match fs::read_to_string(&manifest_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{name}: the output does not match {manifest_path}\n{}",
+                diff(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!(
+                "{name}: {manifest_path} is missing, run with GULAGCLEANER_BLESS=1 to create it"
+            )),
+        }
+    }
+
+    // Manifests left behind by removed documents would silently stop being checked.
+    for entry in fs::read_dir(GOLDEN_PATH).expect("Failed to list the manifests") {
+        let path = entry.unwrap().path();
+        let name = path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        if !fixtures.iter().any(|fixture| fixture == name) {
+            failures.push(format!(
+                "{}: there is no sample document for this manifest",
+                path.display()
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} sample documents do not match their manifests:\n\n{}",
+        failures.len(),
+        fixtures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_golden_diff_is_readable() {
+    let expected = "method Wuolah\npage 1 delete\npage 2 crop\n";
+    let actual = "method Wuolah\npage 1 keep\npage 2 crop\npage 3 crop\n";
+    assert_eq!(
+        diff(expected, actual),
+        "   2 - page 1 delete\n   2 + page 1 keep\n   4 + page 3 crop\n"
+    );
+    assert!(Path::new(GOLDEN_PATH).is_dir());
+}
@@ -0,0 +1,444 @@
+//! Malformed and hostile documents, which must be rejected or cleaned without panicking.
+//!
+//! The fuzz targets in `fuzz/` explore the same entry points with coverage guidance; these
+//! tests replay a fixed set of mutations so that every run checks the same inputs.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+use super::fixtures::{
+    naive_document, studocu_document, wuolah_document, NaivePage, StuDocuPage, WuolahPage,
+};
+use crate::{
+    clean::{clean_pdf_report, detect_pdf, CleanOptions},
+    error::CleanError,
+    limits::{Limit, Limits},
+    metadata::extract_metadata,
+    models::page_type::FULL_PAGE_DIMS,
+};
+
+fn save(mut doc: Document) -> Vec<u8> {
+    let mut data = Vec::new();
+    doc.save_to(&mut data).expect("Failed to save the fixture");
+    data
+}
+
+/// A small deterministic generator, so that failures can be replayed.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize
+    }
+}
+
+/// Truncated copies of `data`, and copies with a few bytes overwritten.
+fn mutations(data: &[u8], count: usize, seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = Lcg(seed);
+    let mut mutants = Vec::new();
+    for i in 0..count {
+        let mut mutant = data.to_vec();
+        if i % 4 == 0 {
+            mutant.truncate(rng.next() % data.len());
+        } else {
+            for _ in 0..1 + rng.next() % 8 {
+                let at = rng.next() % mutant.len();
+                mutant[at] = match rng.next() % 3 {
+                    0 => mutant[at] ^ (1 << (rng.next() % 8)),
+                    // Digits and delimiters shift offsets, lengths and object numbers around.
+                    1 => b"0123456789"[rng.next() % 10],
+                    _ => b"<>[]()/ R"[rng.next() % 9],
+                };
+            }
+        }
+        mutants.push(mutant);
+    }
+    mutants
+}
+
+/// Runs every entry point on `data`, returning the panic message if any of them panicked.
+fn find_panic(data: &[u8]) -> Option<String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let options = CleanOptions::default();
+        let _ = detect_pdf(data, &options);
+        let _ = extract_metadata(data);
+        let _ = clean_pdf_report(data, &options);
+        let _ = clean_pdf_report(
+            data,
+            &CleanOptions {
+                force_naive: true,
+                ..Default::default()
+            },
+        );
+    }));
+    result.err().map(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|x| x.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default()
+    })
+}
+
+#[test]
+fn test_mutated_documents_do_not_panic() {
+    let originals = [
+        save(wuolah_document(&[
+            WuolahPage::Content,
+            WuolahPage::FullPageAd,
+            WuolahPage::Content,
+            WuolahPage::Content,
+        ])),
+        save(studocu_document(&[
+            StuDocuPage::Cover,
+            StuDocuPage::Content,
+            StuDocuPage::Content,
+        ])),
+        save(naive_document(&[
+            NaivePage::Banners((247, 1414), (1753, 170)),
+            NaivePage::FullPageAd(FULL_PAGE_DIMS[0]),
+            NaivePage::Footer,
+        ])),
+        std::fs::read("example_docs/wuolah-031024-example.pdf").expect("Failed to read PDF"),
+    ];
+
+    let mut failures = Vec::new();
+    for (i, original) in originals.iter().enumerate() {
+        let count = if original.len() > 100_000 { 24 } else { 200 };
+        for (j, mutant) in mutations(original, count, i as u64).iter().enumerate() {
+            if let Some(message) = find_panic(mutant) {
+                failures.push(format!("document {i}, mutation {j}: {message}"));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_garbage_does_not_panic() {
+    let inputs: [&[u8]; 6] = [
+        b"",
+        b"%PDF-1.7",
+        b"%PDF-1.7\n%%EOF",
+        b"%PDF-1.4\ntrailer\n<< /Root 1 0 R >>\n%%EOF",
+        b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 1 0 R >>\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF",
+        &[0xff; 1024],
+    ];
+    for input in inputs {
+        assert_eq!(find_panic(input), None, "{input:?}");
+    }
+}
+
+fn assert_limit(result: Result<impl std::fmt::Debug, CleanError>, expected: Limit) {
+    match result {
+        Err(CleanError::LimitExceeded { limit, .. }) => assert_eq!(limit, expected),
+        other => panic!("Expected the {expected} limit to be exceeded, got {other:?}"),
+    }
+}
+
+fn with_limits(limits: Limits) -> CleanOptions {
+    CleanOptions {
+        limits,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_decompression_bomb_is_rejected() {
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    let mut bomb = Stream::new(dictionary! {}, vec![0; 64 * 1024 * 1024]);
+    bomb.compress().expect("Failed to compress the bomb");
+    assert!(bomb.content.len() < 1024 * 1024);
+    doc.add_object(bomb);
+    let data = save(doc);
+
+    let options = with_limits(Limits {
+        max_decompressed_bytes: 16 * 1024 * 1024,
+        ..Default::default()
+    });
+    assert_limit(clean_pdf_report(&data, &options), Limit::DecompressedBytes);
+    assert_limit(detect_pdf(&data, &options), Limit::DecompressedBytes);
+    assert!(clean_pdf_report(&data, &CleanOptions::default()).is_ok());
+}
+
+fn encode_ascii85(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for chunk in data.chunks(4) {
+        let mut group = [0; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        let mut digits = [0; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = b'!' + (value % 85) as u8;
+            value /= 85;
+        }
+        encoded.extend_from_slice(&digits[..chunk.len() + 1]);
+    }
+    encoded.extend_from_slice(b"~>");
+    encoded
+}
+
+#[test]
+fn test_filter_chain_bomb_is_rejected() {
+    let mut deflated = Stream::new(dictionary! {}, vec![0; 64 * 1024 * 1024]);
+    deflated.compress().expect("Failed to compress the bomb");
+    let hex: Vec<u8> = deflated
+        .content
+        .iter()
+        .flat_map(|x| format!("{x:02X}").into_bytes())
+        .collect();
+    let lzw = weezl::encode::Encoder::with_tiff_size_switch(weezl::BitOrder::Msb, 8)
+        .encode(&vec![0; 64 * 1024 * 1024])
+        .expect("Failed to compress the bomb");
+    let bombs = [
+        (
+            Object::from(vec!["ASCII85Decode".into(), "FlateDecode".into()]),
+            encode_ascii85(&deflated.content),
+        ),
+        (
+            vec!["ASCIIHexDecode".into(), "FlateDecode".into()].into(),
+            hex,
+        ),
+        ("LZWDecode".into(), lzw),
+    ];
+
+    let options = with_limits(Limits {
+        max_decompressed_bytes: 16 * 1024 * 1024,
+        ..Default::default()
+    });
+    for (filter, content) in bombs {
+        assert!(content.len() < 4 * 1024 * 1024);
+        let mut doc = naive_document(&[NaivePage::Plain]);
+        doc.add_object(Stream::new(dictionary! { "Filter" => filter }, content));
+        let data = save(doc);
+        assert_limit(clean_pdf_report(&data, &options), Limit::DecompressedBytes);
+        assert_limit(detect_pdf(&data, &options), Limit::DecompressedBytes);
+    }
+}
+
+#[test]
+fn test_too_many_objects_are_rejected() {
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    for i in 0..1000 {
+        doc.add_object(Object::Integer(i));
+    }
+    let data = save(doc);
+
+    let options = with_limits(Limits {
+        max_objects: 500,
+        ..Default::default()
+    });
+    assert_limit(clean_pdf_report(&data, &options), Limit::Objects);
+    assert!(clean_pdf_report(&data, &CleanOptions::default()).is_ok());
+}
+
+/// Adds a chain of `depth` Form XObjects, each drawing the next, to the first page.
+fn nest_forms(doc: &mut Document, depth: usize) {
+    let mut inner: Option<ObjectId> = None;
+    for _ in 0..depth {
+        let resources = match inner {
+            Some(id) => dictionary! { "XObject" => dictionary! { "Fm0" => id } },
+            None => dictionary! {},
+        };
+        let form = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 1.into(), 1.into()],
+                "Resources" => resources,
+            },
+            b"/Fm0 Do".to_vec(),
+        );
+        inner = Some(doc.add_object(form));
+    }
+    let page = doc.get_pages()[&1];
+    let content = doc.add_object(Stream::new(dictionary! {}, b"/Fm0 Do".to_vec()));
+    let page = doc.get_dictionary_mut(page).expect("The page is missing");
+    page.set("Contents", vec![Object::Reference(content)]);
+    page.set(
+        "Resources",
+        dictionary! { "XObject" => dictionary! { "Fm0" => inner.expect("There are no forms") } },
+    );
+}
+
+#[test]
+fn test_deeply_nested_forms_are_rejected() {
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    nest_forms(&mut doc, 5000);
+    let data = save(doc);
+
+    assert_limit(
+        clean_pdf_report(&data, &CleanOptions::default()),
+        Limit::Depth,
+    );
+    assert_limit(detect_pdf(&data, &CleanOptions::default()), Limit::Depth);
+
+    // Without the limit, walking the forms stops at a fixed depth instead of overflowing.
+    let options = with_limits(Limits {
+        max_depth: usize::MAX,
+        ..Default::default()
+    });
+    assert!(clean_pdf_report(&data, &options).is_ok());
+}
+
+#[test]
+fn test_deep_page_tree_is_rejected() {
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    let root = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .expect("The page tree is missing");
+    // Wrap the root in 100 intermediate nodes with a single kid each.
+    let mut top = root;
+    for _ in 0..100 {
+        let node = doc.add_object(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![Object::Reference(top)],
+            "Count" => 1,
+        });
+        doc.get_dictionary_mut(top)
+            .expect("The node is missing")
+            .set("Parent", node);
+        top = node;
+    }
+    doc.catalog_mut()
+        .expect("The catalog is missing")
+        .set("Pages", top);
+    let data = save(doc);
+
+    assert_limit(
+        clean_pdf_report(&data, &CleanOptions::default()),
+        Limit::Depth,
+    );
+}
+
+#[test]
+fn test_time_budget_is_enforced() {
+    let data = save(naive_document(&[NaivePage::Plain, NaivePage::Footer]));
+
+    let options = with_limits(Limits {
+        time_budget: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    assert_limit(clean_pdf_report(&data, &options), Limit::Time);
+    assert_limit(detect_pdf(&data, &options), Limit::Time);
+
+    let options = with_limits(Limits {
+        time_budget: Some(Duration::from_secs(60)),
+        ..Default::default()
+    });
+    assert!(clean_pdf_report(&data, &options).is_ok());
+}
+
+#[test]
+fn test_shared_forms_are_walked_once() {
+    use crate::models::resources::collect_page_images;
+
+    // 40 levels of two forms, each drawing both forms of the level below, which is 2^40 chains
+    // down to the image at the bottom.
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    let image = doc.add_object(Stream::new(
+        dictionary! { "Subtype" => "Image", "Width" => 1, "Height" => 1 },
+        vec![0],
+    ));
+    let mut below = dictionary! { "Im0" => image };
+    for _ in 0..40 {
+        let level: Vec<ObjectId> = (0..2)
+            .map(|_| {
+                doc.add_object(Stream::new(
+                    dictionary! {
+                        "Subtype" => "Form",
+                        "Resources" => dictionary! { "XObject" => below.clone() },
+                    },
+                    vec![],
+                ))
+            })
+            .collect();
+        below = dictionary! { "Fm0" => level[0], "Fm1" => level[1] };
+    }
+    let page = doc.get_pages()[&1];
+    doc.get_dictionary_mut(page)
+        .expect("The page is missing")
+        .set("Resources", dictionary! { "XObject" => below });
+
+    let images = collect_page_images(&doc, &page).expect("Failed to collect the images");
+    // Once for each of the two forms at the bottom, instead of once for every chain.
+    assert_eq!(images.len(), 2);
+    assert!(images.iter().all(|x| x.id == image && x.forms.len() == 40));
+    assert!(detect_pdf(&save(doc), &CleanOptions::default()).is_ok());
+}
+
+/// Adds a chain of `depth` forms to the first page, each drawing the next one twice, with an
+/// image at the bottom, which places the image 2^depth times.
+fn fan_out_forms(doc: &mut Document, depth: usize) {
+    let image = doc.add_object(Stream::new(
+        dictionary! { "Subtype" => "Image", "Width" => 1, "Height" => 1 },
+        vec![0],
+    ));
+    let mut below = dictionary! { "Im0" => image };
+    let mut content = b"/Im0 Do".to_vec();
+    for _ in 0..depth {
+        let form = doc.add_object(Stream::new(
+            dictionary! {
+                "Subtype" => "Form",
+                "Resources" => dictionary! { "XObject" => below },
+            },
+            content,
+        ));
+        below = dictionary! { "Fm0" => form };
+        content = b"q /Fm0 Do Q q 0.5 0 0 0.5 1 1 cm /Fm0 Do Q".to_vec();
+    }
+    let page = doc.get_pages()[&1];
+    let content = doc.add_object(Stream::new(dictionary! {}, content));
+    let page = doc.get_dictionary_mut(page).expect("The page is missing");
+    page.set("Contents", vec![Object::Reference(content)]);
+    page.set("Resources", dictionary! { "XObject" => below });
+}
+
+#[test]
+fn test_fanned_out_forms_are_walked_once() {
+    use crate::limits::{Budget, MAX_PLACEMENTS};
+    use crate::models::geometry::image_placements;
+
+    let mut doc = naive_document(&[NaivePage::Plain]);
+    fan_out_forms(&mut doc, 40);
+    let page = doc.get_pages()[&1];
+    let budget = Budget::new(&Limits {
+        max_operations: 1_000_000,
+        ..Default::default()
+    });
+    let placements = image_placements(&doc, &page, &budget).expect("Failed to place the images");
+    assert_eq!(placements.len(), MAX_PLACEMENTS);
+    assert!(!budget.is_spent());
+    let data = save(doc);
+
+    // Verifying and previewing walk the forms of every page.
+    let verify = CleanOptions {
+        verify: true,
+        ..Default::default()
+    };
+    let preview = CleanOptions {
+        preview: true,
+        ..Default::default()
+    };
+    for options in [verify, preview] {
+        assert!(clean_pdf_report(&data, &options).is_ok());
+        let options = CleanOptions {
+            limits: Limits {
+                max_operations: 1000,
+                ..Default::default()
+            },
+            ..options
+        };
+        assert_limit(clean_pdf_report(&data, &options), Limit::Operations);
+    }
+}
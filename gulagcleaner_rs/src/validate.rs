@@ -0,0 +1,253 @@
+use std::fmt;
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::{
+    clean::CleanReport,
+    models::{
+        geometry::{as_number, media_box, Rect, PAGE_BOXES},
+        plan::PageAction,
+        resources::page_resources,
+    },
+};
+
+/// The resource categories whose entries are checked to point at existing objects.
+const RESOURCE_CATEGORIES: [&[u8]; 7] = [
+    b"Font",
+    b"XObject",
+    b"ExtGState",
+    b"ColorSpace",
+    b"Pattern",
+    b"Shading",
+    b"Properties",
+];
+
+/// A structural problem found in a cleaned document.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Diagnostic {
+    /// The saved document could not be loaded again. Holds the error of the loader.
+    Unreadable(String),
+    /// The document has no pages.
+    NoPages,
+    /// The document does not have as many pages as the report says were kept.
+    PageCountMismatch { expected: usize, found: usize },
+    /// A page has no media box, not even an inherited one.
+    MissingMediaBox { page: u32 },
+    /// A page box is not an array of four numbers.
+    MalformedBox { page: u32, name: String },
+    /// A page box has no area, or its corners are swapped.
+    DegenerateBox { page: u32, name: String, rect: Rect },
+    /// The `/Contents` of a page refer to an object that does not exist or is not a stream.
+    UnresolvedContents { page: u32, object: ObjectId },
+    /// A page has no resource dictionary, not even an inherited one.
+    MissingResources { page: u32 },
+    /// A resource of a page refers to an object that does not exist.
+    UnresolvedResource {
+        page: u32,
+        name: String,
+        object: ObjectId,
+    },
+}
+
+impl Diagnostic {
+    /// The page the problem was found on, if it is specific to one.
+    pub fn page(&self) -> Option<u32> {
+        match self {
+            Diagnostic::Unreadable(_)
+            | Diagnostic::NoPages
+            | Diagnostic::PageCountMismatch { .. } => None,
+            Diagnostic::MissingMediaBox { page }
+            | Diagnostic::MalformedBox { page, .. }
+            | Diagnostic::DegenerateBox { page, .. }
+            | Diagnostic::UnresolvedContents { page, .. }
+            | Diagnostic::MissingResources { page }
+            | Diagnostic::UnresolvedResource { page, .. } => Some(*page),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Unreadable(e) => write!(f, "the cleaned PDF cannot be read: {e}"),
+            Diagnostic::NoPages => write!(f, "the cleaned PDF has no pages"),
+            Diagnostic::PageCountMismatch { expected, found } => {
+                write!(f, "the cleaned PDF has {found} pages instead of {expected}")
+            }
+            Diagnostic::MissingMediaBox { page } => write!(f, "page {page} has no MediaBox"),
+            Diagnostic::MalformedBox { page, name } => {
+                write!(f, "the {name} of page {page} is not a rectangle")
+            }
+            Diagnostic::DegenerateBox { page, name, rect } => {
+                write!(f, "the {name} of page {page} has no area: {rect:?}")
+            }
+            Diagnostic::UnresolvedContents { page, object } => write!(
+                f,
+                "the contents of page {page} refer to {} {} R, which is not a stream",
+                object.0, object.1
+            ),
+            Diagnostic::MissingResources { page } => write!(f, "page {page} has no resources"),
+            Diagnostic::UnresolvedResource { page, name, object } => write!(
+                f,
+                "the resource {name} of page {page} refers to {} {} R, which does not exist",
+                object.0, object.1
+            ),
+        }
+    }
+}
+
+/// Loads a saved document again and checks its structure against the report it was cleaned
+/// with.
+///
+/// Every page must have boxes with an area, contents that resolve to streams and resources
+/// that resolve to objects, and the document must have the pages the report says were kept.
+/// Returns every problem found, which is empty for a sound document.
+pub fn validate_pdf(data: &[u8], report: &CleanReport) -> Vec<Diagnostic> {
+    match Document::load_mem(data) {
+        Ok(doc) => validate_document(&doc, report),
+        Err(e) => vec![Diagnostic::Unreadable(e.to_string())],
+    }
+}
+
+/// Checks the structure of a cleaned document, like `validate_pdf`.
+pub fn validate_document(doc: &Document, report: &CleanReport) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let pages = doc.get_pages();
+    if pages.is_empty() {
+        diagnostics.push(Diagnostic::NoPages);
+    }
+    // Previews keep every page and only outline the ones that would be deleted. They are the
+    // only saved documents whose report has no attempts.
+    let deleted = report
+        .pages
+        .iter()
+        .filter(|plan| plan.action == PageAction::Delete)
+        .count();
+    let expected = match report.attempts.is_empty() {
+        true => report.pages.len(),
+        false => report.pages.len() - deleted,
+    };
+    if pages.len() != expected {
+        diagnostics.push(Diagnostic::PageCountMismatch {
+            expected,
+            found: pages.len(),
+        });
+    }
+
+    for (number, id) in pages {
+        check_boxes(doc, number, &id, &mut diagnostics);
+        check_contents(doc, number, &id, &mut diagnostics);
+        check_resources(doc, number, &id, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn read_rect(doc: &Document, object: &Object) -> Option<Rect> {
+    let numbers: Vec<f32> = doc
+        .dereference(object)
+        .ok()?
+        .1
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|x| as_number(x).ok())
+        .collect::<Option<_>>()?;
+    numbers.try_into().ok()
+}
+
+fn check_rect(page: u32, name: &str, rect: Rect, diagnostics: &mut Vec<Diagnostic>) {
+    let [llx, lly, urx, ury] = rect;
+    if !(urx > llx && ury > lly) {
+        diagnostics.push(Diagnostic::DegenerateBox {
+            page,
+            name: name.to_string(),
+            rect,
+        });
+    }
+}
+
+fn check_boxes(doc: &Document, page: u32, id: &ObjectId, diagnostics: &mut Vec<Diagnostic>) {
+    let Ok(dict) = doc.get_dictionary(*id) else {
+        return;
+    };
+    match media_box(doc, id) {
+        Ok(rect) => check_rect(page, "MediaBox", rect, diagnostics),
+        Err(_) if dict.has(b"MediaBox") => diagnostics.push(Diagnostic::MalformedBox {
+            page,
+            name: "MediaBox".to_string(),
+        }),
+        Err(_) => diagnostics.push(Diagnostic::MissingMediaBox { page }),
+    }
+    // The media box may be inherited, and was checked above.
+    for name in PAGE_BOXES.iter().filter(|name| **name != "MediaBox") {
+        let Ok(object) = dict.get(name.as_bytes()) else {
+            continue;
+        };
+        match read_rect(doc, object) {
+            Some(rect) => check_rect(page, name, rect, diagnostics),
+            None => diagnostics.push(Diagnostic::MalformedBox {
+                page,
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+fn check_contents(doc: &Document, page: u32, id: &ObjectId, diagnostics: &mut Vec<Diagnostic>) {
+    let Ok(contents) = doc.get_dictionary(*id).and_then(|x| x.get(b"Contents")) else {
+        // A page without contents is blank, which is valid.
+        return;
+    };
+    let references: Vec<ObjectId> = match contents {
+        Object::Reference(reference) => match doc.get_object(*reference) {
+            Ok(Object::Array(array)) => {
+                array.iter().filter_map(|x| x.as_reference().ok()).collect()
+            }
+            _ => vec![*reference],
+        },
+        Object::Array(array) => array.iter().filter_map(|x| x.as_reference().ok()).collect(),
+        _ => vec![],
+    };
+    for object in references {
+        if doc.get_object(object).and_then(Object::as_stream).is_err() {
+            diagnostics.push(Diagnostic::UnresolvedContents { page, object });
+        }
+    }
+}
+
+fn check_resources(doc: &Document, page: u32, id: &ObjectId, diagnostics: &mut Vec<Diagnostic>) {
+    let Ok(Some(dict)) = page_resources(doc, id) else {
+        diagnostics.push(Diagnostic::MissingResources { page });
+        return;
+    };
+    let mut check_entries = |category: &[u8], entries: &Dictionary| {
+        for (key, value) in entries.iter() {
+            let Ok(object) = value.as_reference() else {
+                continue;
+            };
+            if doc.get_object(object).is_err() {
+                diagnostics.push(Diagnostic::UnresolvedResource {
+                    page,
+                    name: format!(
+                        "/{}/{}",
+                        String::from_utf8_lossy(category),
+                        String::from_utf8_lossy(key)
+                    ),
+                    object,
+                });
+            }
+        }
+    };
+    for category in RESOURCE_CATEGORIES {
+        let Ok(entries) = dict
+            .get(category)
+            .and_then(|x| doc.dereference(x))
+            .and_then(|x| x.1.as_dict())
+        else {
+            continue;
+        };
+        check_entries(category, entries);
+    }
+}
@@ -0,0 +1,295 @@
+use lopdf::{content::Content, Document, Object, ObjectId};
+
+use crate::{
+    limits::Budget,
+    metadata::page_text_lines,
+    models::{
+        geometry::image_placements,
+        plan::{PageAction, PagePlan},
+        resources::PageImage,
+        signature::AdSignatures,
+    },
+};
+
+/// Pages with fewer visible characters than this are treated as having no real text.
+const MIN_TEXT_CHARS: usize = 50;
+/// Deleting a page with at least this many characters of text is suspicious.
+const DELETED_WARN_CHARS: usize = 200;
+/// Deleting a page with at least this many characters of text almost certainly lost content.
+const DELETED_FAIL_CHARS: usize = 1000;
+
+/// How confident we are that cleaning kept all the real content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Verdict {
+    /// Nothing suggests that content was lost.
+    Pass,
+    /// Some content changed in a way that may be legitimate, and is worth a look.
+    Warn,
+    /// Real content was almost certainly lost.
+    Fail,
+}
+
+/// What a page shows: how much text it has and how many non-ad images it draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PageStats {
+    /// The number of non-whitespace characters of text on the page.
+    pub text_chars: usize,
+    /// The number of images drawn on the page that are not ads or logos.
+    pub images: usize,
+}
+
+/// The verification of a single page of the original document.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PageCheck {
+    /// The 1-based page number in the original document.
+    pub number: u32,
+    /// The page in the cleaned document, if it was kept.
+    pub cleaned_number: Option<u32>,
+    pub before: PageStats,
+    /// What the page shows after cleaning, if it was kept.
+    pub after: Option<PageStats>,
+    pub verdict: Verdict,
+    /// Why the page did not pass, empty if it did.
+    pub reasons: Vec<String>,
+}
+
+/// The result of comparing a cleaned document with its original.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Verification {
+    /// The worst verdict of all the pages.
+    pub verdict: Verdict,
+    pub pages: Vec<PageCheck>,
+}
+
+/// Measures what a page shows.
+///
+/// Images that match one of the ad signatures, that are in `hidden`, or whose height was set
+/// to zero to hide them are not counted.
+pub fn page_stats(
+    doc: &Document,
+    page: &ObjectId,
+    signatures: &AdSignatures,
+    hidden: &[ObjectId],
+    budget: &Budget,
+) -> PageStats {
+    // Reading the text of a single page, unlike `Document::extract_text`, does not walk the
+    // whole page tree.
+    let text = page_text_lines(doc, *page).unwrap_or_default().concat();
+    let text_chars = text.chars().filter(|c| !c.is_whitespace()).count();
+
+    let all_signatures = [
+        &signatures.horizontal_banners,
+        &signatures.vertical_banners,
+        &signatures.full_page,
+        &signatures.logos,
+    ];
+    let images = image_placements(doc, page, budget)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|placement| !hidden.contains(&placement.id))
+        .filter_map(|placement| {
+            let dict = &doc.get_object(placement.id).ok()?.as_stream().ok()?.dict;
+            let dim = |key: &[u8]| dict.get(key).and_then(Object::as_i64).unwrap_or(0);
+            Some(PageImage {
+                id: placement.id,
+                name: vec![],
+                height: dim(b"Height"),
+                width: dim(b"Width"),
+                forms: vec![],
+            })
+        })
+        .filter(|image| image.height > 0 && image.width > 0)
+        .filter(|image| {
+            !all_signatures
+                .iter()
+                .flat_map(|x| x.iter())
+                .any(|signature| signature.matches(doc, image))
+        })
+        .count();
+
+    PageStats { text_chars, images }
+}
+
+/// Whether a page draws nothing at all: no text, no paths, no shadings and no XObjects.
+///
+/// Pages whose content cannot be parsed are not considered blank, as there is no telling what
+/// they draw.
+pub fn is_blank(doc: &Document, page: &ObjectId) -> bool {
+    let Ok(content) = doc.get_page_content(*page) else {
+        return false;
+    };
+    let Ok(content) = Content::decode(&content) else {
+        return false;
+    };
+    !content.operations.iter().any(|operation| {
+        matches!(
+            operation.operator.as_str(),
+            "Tj" | "TJ"
+                | "'"
+                | "\""
+                | "Do"
+                | "f"
+                | "F"
+                | "f*"
+                | "S"
+                | "s"
+                | "B"
+                | "B*"
+                | "b"
+                | "b*"
+                | "sh"
+                | "BI"
+        )
+    })
+}
+
+/// Measures every page of a document, before it is cleaned according to `plans`.
+pub fn snapshot(
+    doc: &Document,
+    plans: &[PagePlan],
+    signatures: &AdSignatures,
+    budget: &Budget,
+) -> Vec<PageStats> {
+    plans
+        .iter()
+        .map(|plan| page_stats(doc, &plan.id, signatures, &plan.logos, budget))
+        .collect()
+}
+
+/// Compares an original and a cleaned document page by page.
+///
+/// `plans` are the plans the original was cleaned with; the pages that were not deleted are
+/// expected to appear in the cleaned document in the same order.
+pub fn verify(
+    original: &Document,
+    cleaned: &Document,
+    plans: &[PagePlan],
+    signatures: &AdSignatures,
+) -> Verification {
+    let budget = Budget::default();
+    compare(
+        &snapshot(original, plans, signatures, &budget),
+        cleaned,
+        plans,
+        signatures,
+        &budget,
+    )
+}
+
+/// Compares the measurements taken by `snapshot` before cleaning with the cleaned document.
+pub fn compare(
+    before: &[PageStats],
+    cleaned: &Document,
+    plans: &[PagePlan],
+    signatures: &AdSignatures,
+    budget: &Budget,
+) -> Verification {
+    let cleaned_pages = cleaned.get_pages();
+    let mut kept = 0;
+
+    let pages: Vec<PageCheck> = plans
+        .iter()
+        .zip(before)
+        .map(|(plan, before)| {
+            let cleaned_number = match plan.action {
+                PageAction::Delete => None,
+                _ => {
+                    kept += 1;
+                    Some(kept)
+                }
+            };
+            let after = cleaned_number.and_then(|number| {
+                let id = cleaned_pages.get(&number)?;
+                Some(page_stats(cleaned, id, signatures, &[], budget))
+            });
+            let (verdict, reasons) = check_page(before, cleaned_number, after.as_ref());
+            PageCheck {
+                number: plan.number,
+                cleaned_number,
+                before: *before,
+                after,
+                verdict,
+                reasons,
+            }
+        })
+        .collect();
+
+    let mut verdict = pages
+        .iter()
+        .map(|page| page.verdict)
+        .max()
+        .unwrap_or(Verdict::Pass);
+    if cleaned_pages.is_empty() && !plans.is_empty() {
+        verdict = Verdict::Fail;
+    }
+
+    Verification { verdict, pages }
+}
+
+fn check_page(
+    before: &PageStats,
+    cleaned_number: Option<u32>,
+    after: Option<&PageStats>,
+) -> (Verdict, Vec<String>) {
+    let mut verdict = Verdict::Pass;
+    let mut reasons = Vec::new();
+    let mut flag = |level: Verdict, reason: String| {
+        verdict = verdict.max(level);
+        reasons.push(reason);
+    };
+
+    match (cleaned_number, after) {
+        (None, _) => {
+            if before.text_chars >= DELETED_FAIL_CHARS {
+                flag(
+                    Verdict::Fail,
+                    format!("deleted page had {} characters of text", before.text_chars),
+                );
+            } else if before.text_chars >= DELETED_WARN_CHARS || before.images > 0 {
+                flag(
+                    Verdict::Warn,
+                    format!(
+                        "deleted page had {} characters of text and {} images",
+                        before.text_chars, before.images
+                    ),
+                );
+            }
+        }
+        (Some(number), None) => flag(
+            Verdict::Fail,
+            format!("page {number} is missing from the cleaned document"),
+        ),
+        (Some(_), Some(after)) => {
+            if before.text_chars >= MIN_TEXT_CHARS && after.text_chars == 0 {
+                flag(Verdict::Fail, "all the text disappeared".to_string());
+            } else if after.text_chars * 2 < before.text_chars {
+                flag(
+                    Verdict::Warn,
+                    format!(
+                        "text shrank from {} to {} characters",
+                        before.text_chars, after.text_chars
+                    ),
+                );
+            }
+
+            if before.images > 0 && after.images == 0 && before.text_chars < MIN_TEXT_CHARS {
+                flag(Verdict::Fail, "all the images disappeared".to_string());
+            } else if after.images < before.images {
+                flag(
+                    Verdict::Warn,
+                    format!(
+                        "{} of {} images disappeared",
+                        before.images - after.images,
+                        before.images
+                    ),
+                );
+            }
+        }
+    }
+
+    (verdict, reasons)
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use notify::{RecursiveMode, Watcher};
+
+use crate::{clean::CleanOptions, error::CleanError, io::clean_path};
+
+/// The suffix added to the name of the cleaned copy of a file saved alongside the original.
+pub const CLEAN_SUFFIX: &str = "_clean";
+
+/// Where the cleaned copies of the watched files are saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLocation {
+    /// In this directory, with the same file name as the original.
+    Directory(PathBuf),
+    /// Next to the original, with `CLEAN_SUFFIX` added to its name.
+    Alongside,
+}
+
+/// Options of a folder watcher.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// The directories that are watched, including their subdirectories.
+    pub dirs: Vec<PathBuf>,
+    /// Where the cleaned copies are saved.
+    pub output: OutputLocation,
+    /// The journal file that records which files were already processed.
+    pub journal: PathBuf,
+    /// How long a file must stay the same size before it is considered completely written.
+    pub settle: Duration,
+    /// The options used to clean every file.
+    pub clean: CleanOptions,
+}
+
+impl WatchOptions {
+    /// Watches `dirs`, saving the cleaned copies alongside the originals and keeping the journal
+    /// in `.gulagcleaner-journal` in the first directory.
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        let journal = dirs
+            .first()
+            .map(|dir| dir.join(".gulagcleaner-journal"))
+            .unwrap_or_else(|| PathBuf::from(".gulagcleaner-journal"));
+        WatchOptions {
+            dirs,
+            output: OutputLocation::Alongside,
+            journal,
+            settle: Duration::from_secs(2),
+            clean: CleanOptions::default(),
+        }
+    }
+}
+
+/// What happened to a file the watcher processed.
+#[derive(Debug)]
+pub struct WatchEvent {
+    /// The file that was processed.
+    pub input: PathBuf,
+    /// Where the cleaned copy was saved.
+    pub output: PathBuf,
+    /// The name of the method used, or the error that prevented the file from being cleaned.
+    pub result: Result<&'static str, CleanError>,
+}
+
+/// The size and modification time of a file, which identify a version of it.
+type Version = (u64, u64);
+
+fn version(path: &Path) -> io::Result<Version> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    Ok((metadata.len(), modified))
+}
+
+/// The record of the files that were processed, kept in an append-only text file.
+///
+/// Each line holds the size and modification time of a file when it was processed, whether it
+/// was cleaned, and its path, separated by tabs. A file is processed again only if it changes.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    entries: HashMap<PathBuf, Version>,
+}
+
+impl Journal {
+    /// Opens a journal, reading the entries recorded by previous runs if the file exists.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut entries = HashMap::new();
+        match fs::File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    let mut fields = line.splitn(4, '\t');
+                    let (Some(size), Some(modified), Some(_), Some(file)) =
+                        (fields.next(), fields.next(), fields.next(), fields.next())
+                    else {
+                        continue;
+                    };
+                    if let (Ok(size), Ok(modified)) = (size.parse(), modified.parse()) {
+                        entries.insert(PathBuf::from(file), (size, modified));
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Journal { path, entries })
+    }
+
+    /// Whether this version of a file was already processed.
+    pub fn contains(&self, file: &Path, version: Version) -> bool {
+        self.entries.get(file) == Some(&version)
+    }
+
+    /// Records that a version of a file was processed, with the outcome.
+    pub fn record(&mut self, file: &Path, version: Version, outcome: &str) -> io::Result<()> {
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // Keep every entry on a single line.
+        let outcome = outcome.replace(['\t', '\n', '\r'], " ");
+        writeln!(
+            journal,
+            "{}\t{}\t{}\t{}",
+            version.0,
+            version.1,
+            outcome,
+            file.display()
+        )?;
+        journal.sync_data()?;
+        self.entries.insert(file.to_path_buf(), version);
+        Ok(())
+    }
+}
+
+/// A file that was seen but may still be being written.
+#[derive(Debug)]
+struct Pending {
+    version: Version,
+    since: Instant,
+}
+
+/// Cleans the PDFs that appear in a set of directories.
+///
+/// Files are only cleaned once their size and modification time stop changing for the settle
+/// time, and every processed file is recorded in the journal so that it is not processed again,
+/// even after a restart.
+#[derive(Debug)]
+pub struct FolderWatcher {
+    options: WatchOptions,
+    journal: Journal,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl FolderWatcher {
+    /// Creates a watcher, reading the journal of previous runs.
+    ///
+    /// The watched directories, the output directory and the journal are made absolute, so that
+    /// they can be compared with the paths reported by the file system.
+    pub fn new(mut options: WatchOptions) -> Result<Self, CleanError> {
+        for dir in &mut options.dirs {
+            *dir = fs::canonicalize(&*dir)?;
+        }
+        if let OutputLocation::Directory(dir) = &mut options.output {
+            fs::create_dir_all(&*dir)?;
+            *dir = fs::canonicalize(&*dir)?;
+        }
+        if let (Some(parent), Some(name)) = (options.journal.parent(), options.journal.file_name())
+        {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            options.journal = fs::canonicalize(parent)?.join(name);
+        }
+        let journal = Journal::open(&options.journal)?;
+        Ok(FolderWatcher {
+            options,
+            journal,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Where the cleaned copy of a file is saved.
+    pub fn output_path(&self, input: &Path) -> PathBuf {
+        match &self.options.output {
+            OutputLocation::Directory(dir) => dir.join(input.file_name().unwrap_or_default()),
+            OutputLocation::Alongside => {
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                input.with_file_name(format!("{stem}{CLEAN_SUFFIX}.pdf"))
+            }
+        }
+    }
+
+    /// Whether a path is a PDF that should be cleaned, rather than an output, the journal or
+    /// some other file.
+    fn is_candidate(&self, path: &Path) -> bool {
+        let is_pdf = path
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("pdf"));
+        let is_hidden = path
+            .file_name()
+            .is_some_and(|x| x.to_string_lossy().starts_with('.'));
+        let is_output = match &self.options.output {
+            OutputLocation::Directory(dir) => path.starts_with(dir),
+            OutputLocation::Alongside => path
+                .file_stem()
+                .is_some_and(|x| x.to_string_lossy().ends_with(CLEAN_SUFFIX)),
+        };
+        is_pdf && !is_hidden && !is_output && path != self.options.journal
+    }
+
+    /// Notes that a file was created or changed, so that it is cleaned once it settles.
+    pub fn notice(&mut self, path: &Path, now: Instant) {
+        if !self.is_candidate(path) {
+            return;
+        }
+        let Ok(version) = version(path) else {
+            return;
+        };
+        if self.journal.contains(path, version) {
+            self.pending.remove(path);
+            return;
+        }
+        match self.pending.get_mut(path) {
+            Some(pending) if pending.version == version => {}
+            Some(pending) => {
+                pending.version = version;
+                pending.since = now;
+            }
+            None => {
+                self.pending.insert(
+                    path.to_path_buf(),
+                    Pending {
+                        version,
+                        since: now,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Notes every PDF currently in the watched directories, to catch up on the files that
+    /// appeared while the watcher was not running.
+    pub fn scan(&mut self, now: Instant) -> io::Result<()> {
+        let mut dirs = self.options.dirs.clone();
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    let is_output = matches!(
+                        &self.options.output,
+                        OutputLocation::Directory(output) if path.starts_with(output)
+                    );
+                    if !is_output {
+                        dirs.push(path);
+                    }
+                } else {
+                    self.notice(&path, now);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Cleans the noticed files that have not changed for the settle time.
+    pub fn process_ready(&mut self, now: Instant) -> Vec<WatchEvent> {
+        let paths: Vec<PathBuf> = self.pending.keys().cloned().collect();
+        let mut events = Vec::new();
+        for path in paths {
+            // Check the file again, it may have changed since it was last noticed.
+            self.notice(&path, now);
+            let Some(pending) = self.pending.get(&path) else {
+                continue;
+            };
+            if now.duration_since(pending.since) < self.options.settle {
+                continue;
+            }
+            let version = pending.version;
+            self.pending.remove(&path);
+
+            let output = self.output_path(&path);
+            let result =
+                clean_path(&path, &output, &self.options.clean).map(|report| report.method);
+            let outcome = match &result {
+                Ok(method) => format!("cleaned with {method}"),
+                Err(e) => format!("failed: {e}"),
+            };
+            let result = match self.journal.record(&path, version, &outcome) {
+                Ok(()) => result,
+                Err(e) => Err(e.into()),
+            };
+            events.push(WatchEvent {
+                input: path,
+                output,
+                result,
+            });
+        }
+        events
+    }
+
+    /// Watches the directories until an error happens, calling `on_event` for each processed file.
+    ///
+    /// The directories are scanned once on start, and again every `poll` in case some
+    /// notifications were missed.
+    pub fn run(
+        &mut self,
+        poll: Duration,
+        mut on_event: impl FnMut(&WatchEvent),
+    ) -> Result<(), CleanError> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(notify_error)?;
+        for dir in &self.options.dirs {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .map_err(notify_error)?;
+        }
+
+        self.scan(Instant::now())?;
+        let mut last_scan = Instant::now();
+        loop {
+            match receiver.recv_timeout(poll.min(self.options.settle)) {
+                Ok(event) => {
+                    for path in event.map_err(notify_error)?.paths {
+                        self.notice(&path, Instant::now());
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+
+            if last_scan.elapsed() >= poll {
+                self.scan(Instant::now())?;
+                last_scan = Instant::now();
+            }
+            for event in self.process_ready(Instant::now()) {
+                on_event(&event);
+            }
+        }
+    }
+}
+
+fn notify_error(e: notify::Error) -> CleanError {
+    CleanError::Io(io::Error::other(e))
+}
@@ -0,0 +1,270 @@
+/*!
+ # GulagCleaner HTTP service
+
+ A small HTTP server that cleans PDFs with `gulagcleaner_rs`, for tools that cannot run the
+ WASM build in a browser.
+
+ | Endpoint         | Body | Response                                              |
+ |------------------|------|-------------------------------------------------------|
+ | `POST /clean`    | PDF  | The cleaned PDF, with the method in `X-Gulagcleaner-Method` |
+ | `POST /detect`   | PDF  | JSON report of the method and what would be done to each page |
+ | `POST /metadata` | PDF  | JSON details printed on the cover page                |
+ | `GET /health`    |      | JSON status of the server                             |
+
+ `POST /clean` and `POST /detect` take an optional `force_naive=true` query parameter. Errors
+ are returned as JSON objects with an `error` field.
+*/
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+    net::SocketAddr,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use gulagcleaner_rs::{
+    clean_pdf_report, detect_pdf, metadata::extract_metadata, CleanError, CleanOptions, Limits,
+};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response};
+
+/// Limits and address of the server.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The address to listen on, such as `127.0.0.1:8080`.
+    pub addr: String,
+    /// The largest request body accepted, in bytes.
+    pub max_body_bytes: usize,
+    /// How long a request may take to be handled before it is answered with a timeout. It is
+    /// also the time budget of the cleaner, which stops working on the document after it.
+    pub timeout: Duration,
+    /// The maximum number of documents handled at the same time. Requests over the limit are
+    /// turned away instead of queued.
+    pub max_concurrency: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: "127.0.0.1:8080".to_string(),
+            max_body_bytes: 50 * 1024 * 1024,
+            timeout: Duration::from_secs(60),
+            max_concurrency: thread::available_parallelism().map_or(4, |x| x.get()),
+        }
+    }
+}
+
+/// The HTTP cleaning service.
+pub struct Server {
+    http: Arc<tiny_http::Server>,
+    config: Config,
+    active: Arc<AtomicUsize>,
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+impl Server {
+    /// Starts listening on the configured address.
+    pub fn bind(config: Config) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let http = tiny_http::Server::http(&config.addr)?;
+        Ok(Server {
+            http: Arc::new(http),
+            config,
+            active: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Makes `run` return once the request it is waiting for, if any, is handled.
+    pub fn stop(&self) {
+        self.http.unblock();
+    }
+
+    /// Answers requests until `stop` is called, each one on its own thread.
+    pub fn run(&self) {
+        for mut request in self.http.incoming_requests() {
+            let config = self.config.clone();
+            let active = self.active.clone();
+            thread::spawn(move || {
+                let response = route(&mut request, &config, &active);
+                // The client may have gone away already, there is nobody to report it to.
+                let _ = request.respond(response);
+            });
+        }
+    }
+}
+
+/// Holds one of the concurrency slots until it is dropped.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn acquire(active: &Arc<AtomicUsize>, max: usize) -> Option<Slot> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Slot(active.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// What a request asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Clean,
+    Detect,
+    Metadata,
+}
+
+/// Routes a request and builds its response.
+///
+/// The body is only read once the request is known to be valid and a concurrency slot is free.
+fn route(request: &mut Request, config: &Config, active: &Arc<AtomicUsize>) -> HttpResponse {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let force_naive = query
+        .split('&')
+        .any(|x| x == "force_naive=true" || x == "force_naive=1");
+
+    let endpoint = match (request.method(), path) {
+        (Method::Get, "/health") => {
+            let status = json!({
+                "status": "ok",
+                "active": active.load(Ordering::Acquire),
+                "max_concurrency": config.max_concurrency,
+            });
+            return json_response(200, &status);
+        }
+        (Method::Post, "/clean") => Endpoint::Clean,
+        (Method::Post, "/detect") => Endpoint::Detect,
+        (Method::Post, "/metadata") => Endpoint::Metadata,
+        (_, "/health" | "/clean" | "/detect" | "/metadata") => {
+            return error_response(405, "method not allowed");
+        }
+        _ => return error_response(404, "not found"),
+    };
+
+    let Some(slot) = Slot::acquire(active, config.max_concurrency) else {
+        return error_response(503, "the server is busy");
+    };
+    let data = match read_body(request, config.max_body_bytes) {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+
+    // The document is handled on its own thread so that it can be given up on after the
+    // timeout. The cleaner stops itself once the timeout is over too, and the slot is only
+    // released once the work really stops.
+    let (sender, receiver) = mpsc::channel();
+    let timeout = config.timeout;
+    thread::spawn(move || {
+        let response = panic::catch_unwind(AssertUnwindSafe(|| {
+            run_endpoint(endpoint, &data, force_naive, timeout)
+        }))
+        .unwrap_or_else(|_| error_response(500, "cleaning panicked"));
+        drop(slot);
+        let _ = sender.send(response);
+    });
+
+    receiver
+        .recv_timeout(config.timeout)
+        .unwrap_or_else(|_| error_response(504, "the request timed out"))
+}
+
+fn run_endpoint(
+    endpoint: Endpoint,
+    data: &[u8],
+    force_naive: bool,
+    timeout: Duration,
+) -> HttpResponse {
+    let options = CleanOptions {
+        force_naive,
+        limits: Limits {
+            time_budget: Some(timeout),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    match endpoint {
+        Endpoint::Clean => match clean_pdf_report(data, &options) {
+            Ok((pdf, report)) => Response::from_data(pdf)
+                .with_header(header("Content-Type", "application/pdf"))
+                .with_header(header("X-Gulagcleaner-Method", report.method))
+                .with_header(header(
+                    "X-Gulagcleaner-Method-Code",
+                    &report.method_code.to_string(),
+                )),
+            Err(e) => clean_error_response(&e),
+        },
+        Endpoint::Detect => match detect_pdf(data, &options) {
+            Ok(report) => json_response(200, &json!(report)),
+            Err(e) => clean_error_response(&e),
+        },
+        Endpoint::Metadata => match extract_metadata(data) {
+            Ok(metadata) => json_response(200, &json!(metadata)),
+            Err(e) => clean_error_response(&e),
+        },
+    }
+}
+
+/// Reads the body of a request, refusing it if it is larger than `limit`.
+fn read_body(request: &mut Request, limit: usize) -> Result<Vec<u8>, HttpResponse> {
+    let too_large = || error_response(413, &format!("the body is larger than {limit} bytes"));
+    if request.body_length().is_some_and(|length| length > limit) {
+        return Err(too_large());
+    }
+
+    let mut data = Vec::new();
+    request
+        .as_reader()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| error_response(400, &format!("could not read the body: {e}")))?;
+    if data.len() > limit {
+        return Err(too_large());
+    }
+    Ok(data)
+}
+
+fn clean_error_response(e: &CleanError) -> HttpResponse {
+    let status = match e {
+        CleanError::InvalidPdf(_) => 400,
+        CleanError::Encrypted
+        | CleanError::UnsupportedLayout { .. }
+        | CleanError::LimitExceeded { .. } => 422,
+        _ => 500,
+    };
+    error_response(status, &e.to_string())
+}
+
+fn error_response(status: u16, message: &str) -> HttpResponse {
+    json_response(status, &json!({ "error": message }))
+}
+
+fn json_response(status: u16, body: &serde_json::Value) -> HttpResponse {
+    Response::from_data(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("header names are valid")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+//! Runs the GulagCleaner HTTP service.
+//!
+//! Usage: gulagcleaner-server [-a <addr>] [-m <max_body_mb>] [-t <timeout_secs>] [-c <max_concurrency>]
+
+use std::{env, process, time::Duration};
+
+use gulagcleaner_server::{Config, Server};
+
+const USAGE: &str = "Usage: gulagcleaner-server [-a <addr>] [-m <max_body_mb>] [-t <timeout_secs>] [-c <max_concurrency>]
+
+Serves POST /clean, POST /detect, POST /metadata and GET /health.
+
+Optional arguments:
+  -a <addr>             Address to listen on. Defaults to 127.0.0.1:8080.
+  -m <max_body_mb>      Largest PDF accepted, in megabytes. Defaults to 50.
+  -t <timeout_secs>     Time allowed to handle a request. Defaults to 60.
+  -c <max_concurrency>  Documents handled at the same time. Defaults to the number of CPUs.
+  -h                    Show this help message.";
+
+fn main() {
+    let mut config = Config::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next().unwrap_or_else(|| {
+                eprintln!("{USAGE}");
+                process::exit(2);
+            })
+        };
+        let number = |value: String| -> u64 {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("{value} is not a number.\n\n{USAGE}");
+                process::exit(2);
+            })
+        };
+        match arg.as_str() {
+            "-h" => {
+                println!("{USAGE}");
+                return;
+            }
+            "-a" => config.addr = value(),
+            "-m" => config.max_body_bytes = number(value()) as usize * 1024 * 1024,
+            "-t" => config.timeout = Duration::from_secs(number(value())),
+            "-c" => config.max_concurrency = number(value()).max(1) as usize,
+            _ => {
+                eprintln!("{USAGE}");
+                process::exit(2);
+            }
+        }
+    }
+
+    let server = Server::bind(config).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    });
+    if let Some(addr) = server.local_addr() {
+        println!("Listening on http://{addr}");
+    }
+    server.run();
+}
@@ -0,0 +1,229 @@
+use super::*;
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const EXAMPLE_DOCS: &str = "../gulagcleaner_rs/example_docs";
+
+/// A response as seen by the client.
+struct ClientResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl ClientResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("The body is not JSON")
+    }
+}
+
+/// Starts a server on a free local port, returning it with its address.
+fn start(config: Config) -> (Arc<Server>, SocketAddr) {
+    let server = Arc::new(
+        Server::bind(Config {
+            addr: "127.0.0.1:0".to_string(),
+            ..config
+        })
+        .expect("Failed to start the server"),
+    );
+    let addr = server.local_addr().unwrap();
+    let running = server.clone();
+    thread::spawn(move || running.run());
+    (server, addr)
+}
+
+/// Sends a single HTTP/1.1 request and reads the whole response.
+fn request(addr: SocketAddr, method: &str, path: &str, body: &[u8]) -> ClientResponse {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .unwrap();
+    // The server may answer before reading the whole body, when it refuses it.
+    let _ = stream.write_all(body);
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    let split = response
+        .windows(4)
+        .position(|x| x == b"\r\n\r\n")
+        .expect("The response has no header");
+    let head = String::from_utf8_lossy(&response[..split]).to_string();
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .unwrap()
+        .split(' ')
+        .nth(1)
+        .unwrap()
+        .parse()
+        .unwrap();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+
+    let mut body = response[split + 4..].to_vec();
+    let chunked = headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("Transfer-Encoding") && value.eq_ignore_ascii_case("chunked")
+    });
+    if chunked {
+        body = dechunk(&body);
+    }
+    ClientResponse {
+        status,
+        headers,
+        body,
+    }
+}
+
+fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|x| x == b"\r\n").unwrap();
+        let size =
+            usize::from_str_radix(std::str::from_utf8(&data[..line_end]).unwrap().trim(), 16)
+                .unwrap();
+        if size == 0 {
+            return body;
+        }
+        body.extend_from_slice(&data[line_end + 2..line_end + 2 + size]);
+        data = &data[line_end + 2 + size + 2..];
+    }
+}
+
+#[test]
+fn test_health() {
+    let (server, addr) = start(Config {
+        max_concurrency: 3,
+        ..Default::default()
+    });
+    let response = request(addr, "GET", "/health", b"");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["status"], "ok");
+    assert_eq!(response.json()["max_concurrency"], 3);
+    server.stop();
+}
+
+#[test]
+fn test_clean_returns_the_cleaned_pdf() {
+    let (server, addr) = start(Config::default());
+    let data = fs::read(format!("{EXAMPLE_DOCS}/wuolah-170924-example.pdf")).unwrap();
+
+    let response = request(addr, "POST", "/clean", &data);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("Content-Type"), Some("application/pdf"));
+    assert_eq!(response.header("X-Gulagcleaner-Method"), Some("Wuolah"));
+    assert_eq!(response.header("X-Gulagcleaner-Method-Code"), Some("0"));
+    assert_eq!(
+        response.body,
+        gulagcleaner_rs::clean_pdf(data.clone(), false).unwrap().0
+    );
+
+    let response = request(addr, "POST", "/clean?force_naive=true", &data);
+    assert_eq!(response.header("X-Gulagcleaner-Method"), Some("Naive"));
+    server.stop();
+}
+
+#[test]
+fn test_detect_and_metadata_return_json() {
+    let (server, addr) = start(Config::default());
+    let data = fs::read(format!("{EXAMPLE_DOCS}/wuolah-170924-example.pdf")).unwrap();
+    let pages = lopdf_page_count(&data);
+
+    let response = request(addr, "POST", "/detect", &data);
+    assert_eq!(response.status, 200);
+    let report = response.json();
+    assert_eq!(report["method"], "Wuolah");
+    assert_eq!(report["method_code"], 0);
+    assert_eq!(report["pages"].as_array().unwrap().len(), pages);
+    assert_eq!(report["pages"][0]["number"], 1);
+
+    let response = request(addr, "POST", "/metadata", &data);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["file"], "Tema1-2.pdf");
+    assert_eq!(response.json()["university"], "Universidad de Málaga");
+    server.stop();
+}
+
+fn lopdf_page_count(data: &[u8]) -> usize {
+    gulagcleaner_rs::detect_pdf(data, &Default::default())
+        .unwrap()
+        .pages
+        .len()
+}
+
+#[test]
+fn test_errors_are_json() {
+    let (server, addr) = start(Config {
+        max_body_bytes: 1024,
+        ..Default::default()
+    });
+
+    let response = request(addr, "POST", "/clean", b"not a pdf");
+    assert_eq!(response.status, 400);
+    assert!(response.json()["error"].is_string());
+
+    let response = request(addr, "POST", "/clean", &[b'%'; 2048]);
+    assert_eq!(response.status, 413);
+
+    assert_eq!(request(addr, "GET", "/clean", b"").status, 405);
+    assert_eq!(request(addr, "POST", "/nothing", b"").status, 404);
+    server.stop();
+}
+
+#[test]
+fn test_timeout_and_concurrency_limits() {
+    let (server, addr) = start(Config {
+        timeout: Duration::from_millis(1),
+        max_concurrency: 1,
+        ..Default::default()
+    });
+    let data = fs::read(format!("{EXAMPLE_DOCS}/wuolah-300924-example.pdf")).unwrap();
+
+    // Cleaning takes longer than the timeout, and keeps the only slot busy until it stops.
+    let response = request(addr, "POST", "/clean", &data);
+    assert_eq!(response.status, 504);
+
+    // The cleaner stops itself once the timeout is over, which frees the slot again.
+    let start = Instant::now();
+    while request(addr, "GET", "/health", b"").json()["active"] != 0 {
+        assert!(start.elapsed() < Duration::from_secs(60));
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // While every slot is taken, requests are turned away.
+    let slot = Slot::acquire(&server.active, 1).expect("The slot is still taken");
+    assert_eq!(request(addr, "POST", "/detect", &data).status, 503);
+    drop(slot);
+    server.stop();
+}
+
+#[test]
+fn test_cleaning_stops_at_the_timeout() {
+    let data = fs::read(format!("{EXAMPLE_DOCS}/wuolah-300924-example.pdf")).unwrap();
+    for endpoint in [Endpoint::Clean, Endpoint::Detect] {
+        let response = run_endpoint(endpoint, &data, false, Duration::ZERO);
+        assert_eq!(response.status_code().0, 422);
+        let mut body = String::new();
+        response.into_reader().read_to_string(&mut body).unwrap();
+        assert!(body.contains("time_budget"), "{body}");
+    }
+    let response = run_endpoint(Endpoint::Clean, &data, false, Duration::from_secs(60));
+    assert_eq!(response.status_code().0, 200);
+}
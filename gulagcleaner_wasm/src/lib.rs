@@ -1,21 +1,151 @@
-use gulagcleaner_rs;
-use serde::{Deserialize, Serialize};
+use gulagcleaner_rs::{
+    metadata,
+    models::plan::{PageAction, PagePlan},
+    CleanOptions, CleanReport, Diagnostic, MethodAttempt,
+};
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
-#[derive(Serialize, Deserialize)]
+/// Cleaning jobs that read their input in chunks, for use inside Web Workers.
+mod worker;
+
+pub use worker::CleaningJob;
+
+/// The result of cleaning a PDF, as it is handed to JavaScript.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CleaningResult {
-    result: Vec<u8>,
-    method: u8,
+    /// The cleaned PDF, which becomes a `Uint8Array` instead of an array of numbers.
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+    method: &'static str,
+    method_code: u8,
+    pages: Vec<PageInfo>,
+    /// The methods that were tried, ending with the one that was used.
+    attempts: Vec<MethodAttempt>,
+    /// The problems found in the structure of the cleaned PDF.
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// What was done to a page of the original document.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    /// The 1-based page number in the original document.
+    number: u32,
+    /// How the page was classified, such as "BannerAds" or "FullPageAds".
+    page_type: String,
+    /// A short description of what was done to the page, such as "crop" or "delete".
+    action: String,
+    /// Whether the page was removed from the document.
+    deleted: bool,
+    /// Whether the original content of the page is missing, so only a preview of it is left.
+    incomplete: bool,
+}
+
+impl From<&PagePlan> for PageInfo {
+    fn from(plan: &PagePlan) -> Self {
+        PageInfo {
+            number: plan.number,
+            page_type: format!("{:?}", plan.page_type),
+            action: plan.action.describe(),
+            deleted: plan.action == PageAction::Delete,
+            incomplete: plan.incomplete,
+        }
+    }
+}
+
+/// What cleaning a PDF would do, as it is handed to JavaScript.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectionResult {
+    method: &'static str,
+    method_code: u8,
+    /// The number of pages that would be removed.
+    deleted_pages: usize,
+    pages: Vec<PageInfo>,
+}
+
+impl From<&CleanReport> for DetectionResult {
+    fn from(report: &CleanReport) -> Self {
+        let pages: Vec<PageInfo> = report.pages.iter().map(PageInfo::from).collect();
+        DetectionResult {
+            method: report.method,
+            method_code: report.method_code,
+            deleted_pages: pages.iter().filter(|page| page.deleted).count(),
+            pages,
+        }
+    }
+}
+
+impl CleaningResult {
+    fn new(data: Vec<u8>, report: &CleanReport) -> Self {
+        CleaningResult {
+            data,
+            method: report.method,
+            method_code: report.method_code,
+            pages: report.pages.iter().map(PageInfo::from).collect(),
+            attempts: report.attempts.clone(),
+            diagnostics: report.diagnostics.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Cleans a PDF and returns `{ data, method, methodCode, pages, attempts, diagnostics }`, where
+/// `data` is the cleaned PDF as a `Uint8Array`. Throws an `Error` if the PDF cannot be cleaned.
+#[wasm_bindgen]
+pub fn clean_pdf(data: &[u8], force_naive: bool) -> Result<JsValue, JsError> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let (clean_pdf, report) = gulagcleaner_rs::clean_pdf_report(data, &options)?;
+    to_js(&CleaningResult::new(clean_pdf, &report))
 }
 
+/// Detects how a PDF would be cleaned without cleaning it, and returns
+/// `{ method, methodCode, deletedPages, pages }`. Throws an `Error` if the PDF cannot be read.
 #[wasm_bindgen]
-pub fn clean_pdf(data: Vec<u8>, force_naive: bool) -> Vec<u8> {
-    let (clean_pdf, _) = gulagcleaner_rs::clean_pdf(data, force_naive);
-    // For some reason, the serde serialization messes up our data, so we just return the raw bytes until we find a fix
-    // let example = CleaningResult {
-    //     result: clean_pdf,
-    //     method: method_code,
-    // };
-    // Ok(serde_wasm_bindgen::to_value(&example)?)
-    return clean_pdf;
+pub fn detect_pdf(data: &[u8], force_naive: bool) -> Result<JsValue, JsError> {
+    let options = CleanOptions {
+        force_naive,
+        ..Default::default()
+    };
+    let report = gulagcleaner_rs::detect_pdf(data, &options)?;
+    to_js(&DetectionResult::from(&report))
+}
+
+/// Lists how each page of a PDF is classified and what would be done to it, as the `pages`
+/// of `detect_pdf`. Throws an `Error` if the PDF cannot be read.
+#[wasm_bindgen]
+pub fn classify_pages(data: &[u8]) -> Result<JsValue, JsError> {
+    let report = gulagcleaner_rs::detect_pdf(data, &CleanOptions::default())?;
+    to_js(&report.pages.iter().map(PageInfo::from).collect::<Vec<_>>())
+}
+
+/// Reads the details printed on the cover page of a PDF, and returns
+/// `{ file, author, subject, course, faculty, university }`, where any of them may be `null`.
+/// Throws an `Error` if the PDF cannot be read.
+#[wasm_bindgen]
+pub fn extract_metadata(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&metadata::extract_metadata(data)?)
+}
+
+/// Keeps every page of a PDF and outlines what cleaning it would remove. Returns the same
+/// object as `clean_pdf`, where `data` is the preview PDF.
+#[wasm_bindgen]
+pub fn preview_pdf(data: &[u8], force_naive: bool) -> Result<JsValue, JsError> {
+    let options = CleanOptions {
+        force_naive,
+        preview: true,
+        ..Default::default()
+    };
+    let (preview_pdf, report) = gulagcleaner_rs::clean_pdf_report(data, &options)?;
+    to_js(&CleaningResult::new(preview_pdf, &report))
+}
+
+/// Converts a value to a plain JavaScript object.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    Ok(value.serialize(&serializer)?)
 }
@@ -0,0 +1,138 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gulagcleaner_rs::CleanOptions;
+use js_sys::{Promise, Reflect, Uint8Array};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Blob, ReadableStream, ReadableStreamDefaultReader};
+
+use crate::{to_js, CleaningResult};
+
+/// The state of a job, shared with the streams that are being read into it.
+#[derive(Default)]
+struct JobState {
+    buffer: Vec<u8>,
+    aborted: bool,
+}
+
+/// A cleaning job meant to run inside a Web Worker.
+///
+/// The PDF is pushed in chunks straight into a buffer owned by the WASM module, either by hand
+/// with `push` or by reading a `File`, `Blob` or `ReadableStream`. `finish` then cleans it and
+/// returns the same object as `clean_pdf`, whose `data` has its own `ArrayBuffer` that can be
+/// transferred back to the page without copying:
+///
+/// ```js
+/// const job = new CleaningJob(file.size);
+/// await job.pushBlob(file);
+/// const result = job.finish(false);
+/// postMessage(result, [result.data.buffer]);
+/// ```
+///
+/// `abort` stops a job between chunks. Cleaning itself runs to completion once `finish` is
+/// called, because the worker cannot handle messages meanwhile; terminate the worker to stop it.
+#[wasm_bindgen]
+pub struct CleaningJob {
+    state: Rc<RefCell<JobState>>,
+}
+
+#[wasm_bindgen]
+impl CleaningJob {
+    /// Creates an empty job, reserving room for `expected_length` bytes if it is known.
+    #[wasm_bindgen(constructor)]
+    pub fn new(expected_length: Option<usize>) -> CleaningJob {
+        let state = JobState {
+            buffer: Vec::with_capacity(expected_length.unwrap_or(0)),
+            aborted: false,
+        };
+        CleaningJob {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// The number of bytes received so far.
+    #[wasm_bindgen(getter, js_name = bytesReceived)]
+    pub fn bytes_received(&self) -> usize {
+        self.state.borrow().buffer.len()
+    }
+
+    /// Whether the job was aborted.
+    #[wasm_bindgen(getter)]
+    pub fn aborted(&self) -> bool {
+        self.state.borrow().aborted
+    }
+
+    /// Appends a chunk of the PDF, copying it once, directly into the job's buffer.
+    pub fn push(&self, chunk: &Uint8Array) -> Result<(), JsError> {
+        push_chunk(&mut self.state.borrow_mut(), chunk)
+    }
+
+    /// Reads a whole stream of `Uint8Array` chunks into the job. The returned promise resolves
+    /// once the stream ends, and rejects if it fails or the job is aborted.
+    #[wasm_bindgen(js_name = pushStream)]
+    pub fn push_stream(&self, stream: &ReadableStream) -> Promise {
+        let state = self.state.clone();
+        let reader: ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+        future_to_promise(async move {
+            loop {
+                if state.borrow().aborted {
+                    // Let the source know it can stop producing data.
+                    let _ = reader.cancel();
+                    return Err(JsError::new("the cleaning job was aborted").into());
+                }
+                let chunk = JsFuture::from(reader.read()).await?;
+                if Reflect::get(&chunk, &"done".into())?.is_truthy() {
+                    return Ok(JsValue::UNDEFINED);
+                }
+                let value: Uint8Array = Reflect::get(&chunk, &"value".into())?.dyn_into()?;
+                push_chunk(&mut state.borrow_mut(), &value)?;
+            }
+        })
+    }
+
+    /// Reads a `File` or `Blob` into the job, like `pushStream`.
+    #[wasm_bindgen(js_name = pushBlob)]
+    pub fn push_blob(&self, blob: &Blob) -> Promise {
+        self.push_stream(&blob.stream())
+    }
+
+    /// Stops the job. Streams being read are cancelled, and `push` and `finish` throw.
+    pub fn abort(&self) {
+        let mut state = self.state.borrow_mut();
+        state.aborted = true;
+        state.buffer = Vec::new();
+    }
+
+    /// Cleans the PDF received so far and returns
+    /// `{ data, method, methodCode, pages, attempts, diagnostics }`, like `clean_pdf`: the
+    /// cleaned PDF, the method used, what was done to each page, every method that was tried
+    /// and the problems found in the structure of the output. The job's buffer is freed, so a
+    /// job can only be finished once.
+    pub fn finish(&self, force_naive: bool) -> Result<JsValue, JsError> {
+        let data = {
+            let mut state = self.state.borrow_mut();
+            if state.aborted {
+                return Err(JsError::new("the cleaning job was aborted"));
+            }
+            state.aborted = true;
+            std::mem::take(&mut state.buffer)
+        };
+        let options = CleanOptions {
+            force_naive,
+            ..Default::default()
+        };
+        let (clean_pdf, report) = gulagcleaner_rs::clean_pdf_report(&data, &options)?;
+        drop(data);
+        to_js(&CleaningResult::new(clean_pdf, &report))
+    }
+}
+
+fn push_chunk(state: &mut JobState, chunk: &Uint8Array) -> Result<(), JsError> {
+    if state.aborted {
+        return Err(JsError::new("the cleaning job was aborted"));
+    }
+    let start = state.buffer.len();
+    state.buffer.resize(start + chunk.length() as usize, 0);
+    chunk.copy_to(&mut state.buffer[start..]);
+    Ok(())
+}